@@ -0,0 +1,135 @@
+use super::llm::{LLMProvider, LLMInterface, OutputFormat};
+use crate::core::fine_tuning::FineTuneConfig;
+use super::error::{api_key_from_env, LLMError};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde_json::{json, Value};
+use crate::utils::llm::FromLLMResponse;
+use super::http::{estimate_tokens, request_policy_builders, RequestPolicy};
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+pub const DEFAULT_API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+const JSON_INSTRUCTION: &str = "Respond only with a single valid JSON object and no other text.";
+
+pub struct Anthropic {
+    model: String,
+    temperature: f32,
+    max_tokens: u32,
+    policy: RequestPolicy,
+    api_key_env: String
+}
+
+impl Anthropic {
+    pub fn new(model: String, temperature: f32, max_tokens: u32) -> Self {
+        Self { model, temperature, max_tokens, policy: RequestPolicy::default(), api_key_env: DEFAULT_API_KEY_ENV.to_string() }
+    }
+
+    request_policy_builders!();
+
+    pub fn with_api_key_env(mut self, api_key_env: impl Into<String>) -> Self {
+        self.api_key_env = api_key_env.into();
+        self
+    }
+}
+
+/// Extracts the model output from a Messages API response. Structured output arrives as the
+/// input of the forced tool call and is returned as JSON text, like OpenAI's JSON modes.
+fn extract_content(response: &Value) -> Result<String> {
+    if response["stop_reason"] == "refusal" {
+        return Err(LLMError::ContentFiltered("Model refused to respond".to_string()).into());
+    }
+    let blocks = response["content"].as_array()
+        .context("Failed to extract content from Anthropic API response")?;
+
+    if let Some(tool_use) = blocks.iter().find(|block| block["type"] == "tool_use") {
+        return serde_json::to_string(&tool_use["input"])
+            .context("Failed to serialize tool input from Anthropic API response");
+    }
+
+    let text: String = blocks.iter()
+        .filter(|block| block["type"] == "text")
+        .filter_map(|block| block["text"].as_str())
+        .collect();
+    Ok(text)
+}
+
+impl LLMProvider for Anthropic {
+    fn generate_headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        let api_key = api_key_from_env(&self.api_key_env)?;
+        headers.insert("x-api-key", HeaderValue::from_str(&api_key)
+            .context("Failed to create x-api-key header")?);
+        headers.insert("anthropic-version", HeaderValue::from_static(ANTHROPIC_VERSION));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(headers)
+    }
+
+    fn generate_request_body(&self, sys_prompt: &str, user_prompt: &str, output_format: &OutputFormat) -> Result<Value> {
+        let mut body = json!({
+            "model": self.model,
+            "system": sys_prompt,
+            "messages": [{"role": "user", "content": user_prompt}],
+            "temperature": self.temperature,
+            "max_tokens": self.max_tokens,
+        });
+
+        match output_format {
+            OutputFormat::String => {},
+            OutputFormat::Json => {
+                body["system"] = json!(format!("{}\n\n{}", sys_prompt, JSON_INSTRUCTION));
+            },
+            OutputFormat::StrictJson(schema) => {
+                // Structured output is forced through a single tool whose input schema is the response schema
+                let json_schema = &schema["json_schema"];
+                let name = json_schema["name"].as_str().unwrap_or("response");
+                body["tools"] = json!([{
+                    "name": name,
+                    "description": "Record the response in the required structure.",
+                    "input_schema": json_schema["schema"],
+                }]);
+                body["tool_choice"] = json!({"type": "tool", "name": name});
+            },
+        }
+
+        Ok(body)
+    }
+}
+
+impl LLMInterface for Anthropic {
+    async fn send_request<T: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<T> {
+        let body = self.generate_request_body(sys_prompt, user_prompt, &T::output_format())?;
+        let client = reqwest::Client::new();
+        self.policy.send(
+            "Anthropic",
+            estimate_tokens(sys_prompt, user_prompt, self.max_tokens),
+            || Ok(client.post("https://api.anthropic.com/v1/messages").headers(self.generate_headers()?).json(&body)),
+            |response| Some(response["usage"]["input_tokens"].as_u64()? + response["usage"]["output_tokens"].as_u64()?),
+            extract_content,
+        ).await
+    }
+
+    fn supports_fine_tuning(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> String {
+        format!("anthropic/{}", self.model)
+    }
+
+    fn temperature(&self) -> Option<f32> {
+        Some(self.temperature)
+    }
+
+    async fn upload_file(&self, _file_path: PathBuf) -> Result<Value> {
+        Err(LLMError::InvalidRequest("Anthropic does not support uploading fine-tuning files".to_string()).into())
+    }
+
+    async fn create_fine_tuning_job(&self, _training_file: &str, _validation_file: Option<&str>, _config: &FineTuneConfig) -> Result<Value> {
+        Err(LLMError::InvalidRequest("Anthropic does not support fine-tuning jobs".to_string()).into())
+    }
+
+    async fn train(&self, _file_path: PathBuf, _validation_file_path: Option<PathBuf>, _config: &FineTuneConfig) -> Result<Value> {
+        Err(LLMError::InvalidRequest("Anthropic does not support fine-tuning".to_string()).into())
+    }
+}
@@ -0,0 +1,125 @@
+use super::llm::{LLMProvider, LLMInterface, OutputFormat};
+use crate::core::fine_tuning::FineTuneConfig;
+use super::openai::{Endpoint, OpenAI};
+use crate::utils::rate_limit::RateLimiter;
+use crate::utils::llm::FromLLMResponse;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use tokio::time::Duration;
+use std::sync::Arc;
+use std::path::PathBuf;
+use anyhow::Result;
+
+pub const DEFAULT_API_VERSION: &str = "2024-10-21";
+pub const DEFAULT_API_KEY_ENV: &str = "AZURE_OPENAI_API_KEY";
+
+/// Azure OpenAI resource. Chat requests go to `deployment`, while fine-tuning jobs are created
+/// for the base `model` the deployment serves. Azure serves the OpenAI API, so requests are made
+/// by an `OpenAI` adapter pointed at the resource.
+pub struct AzureOpenAI {
+    inner: OpenAI
+}
+
+impl AzureOpenAI {
+    /// `endpoint` is the resource URL, e.g. `https://my-resource.openai.azure.com`.
+    pub fn new(endpoint: impl Into<String>, deployment: impl Into<String>, model: String, temperature: f32, max_tokens: u32) -> Self {
+        let endpoint = Endpoint::Azure {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            deployment: deployment.into(),
+            api_version: DEFAULT_API_VERSION.to_string(),
+        };
+        Self {
+            inner: OpenAI::new(model, temperature, max_tokens)
+                .with_endpoint(endpoint)
+                .with_api_key_env(DEFAULT_API_KEY_ENV),
+        }
+    }
+
+    pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.inner = self.inner.with_api_version(api_version);
+        self
+    }
+
+    /// How long to wait for Azure to process an uploaded training file before giving up
+    /// (default: 30 minutes).
+    pub fn with_file_timeout(mut self, file_timeout: Duration) -> Self {
+        self.inner = self.inner.with_file_timeout(file_timeout);
+        self
+    }
+
+    pub fn with_api_key_env(mut self, api_key_env: impl Into<String>) -> Self {
+        self.inner = self.inner.with_api_key_env(api_key_env);
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.inner = self.inner.with_rate_limiter(rate_limiter);
+        self
+    }
+
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.inner = self.inner.with_retries(max_retries);
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.inner = self.inner.with_delay(delay);
+        self
+    }
+}
+
+impl LLMProvider for AzureOpenAI {
+    fn generate_headers(&self) -> Result<HeaderMap> {
+        self.inner.generate_headers()
+    }
+
+    fn generate_request_body(&self, sys_prompt: &str, user_prompt: &str, output_format: &OutputFormat) -> Result<Value> {
+        self.inner.generate_request_body(sys_prompt, user_prompt, output_format)
+    }
+}
+
+impl LLMInterface for AzureOpenAI {
+    async fn send_request<T: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<T> {
+        self.inner.send_request(sys_prompt, user_prompt).await
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn temperature(&self) -> Option<f32> {
+        self.inner.temperature()
+    }
+
+    async fn upload_file(&self, file_path: PathBuf) -> Result<Value> {
+        self.inner.upload_file(file_path).await
+    }
+
+    async fn create_fine_tuning_job(&self, training_file: &str, validation_file: Option<&str>, config: &FineTuneConfig) -> Result<Value> {
+        self.inner.create_fine_tuning_job(training_file, validation_file, config).await
+    }
+
+    async fn train(&self, file_path: PathBuf, validation_file_path: Option<PathBuf>, config: &FineTuneConfig) -> Result<Value> {
+        self.inner.train(file_path, validation_file_path, config).await
+    }
+
+    async fn get_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        self.inner.get_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_events(&self, job_id: &str) -> Result<Value> {
+        self.inner.list_fine_tuning_events(job_id).await
+    }
+
+    async fn list_fine_tuning_jobs(&self, limit: u32) -> Result<Value> {
+        self.inner.list_fine_tuning_jobs(limit).await
+    }
+
+    async fn cancel_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        self.inner.cancel_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_checkpoints(&self, job_id: &str) -> Result<Value> {
+        self.inner.list_fine_tuning_checkpoints(job_id).await
+    }
+}
\ No newline at end of file
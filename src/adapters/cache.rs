@@ -0,0 +1,188 @@
+use super::llm::{LLMInterface, OutputFormat};
+use crate::core::fine_tuning::FineTuneConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use crate::utils::llm::FromLLMResponse;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use clap::ValueEnum;
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum CacheMode {
+    /// Serve hits from the cache and store responses to misses
+    #[default]
+    ReadWrite,
+    /// Serve only from the cache; a miss is an error and no request is sent
+    CacheOnly,
+    /// Send every request and overwrite the cached responses
+    Refresh,
+    /// Neither read nor write the cache
+    Bypass,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    provider: String,
+    response: String,
+}
+
+/// Raw response text, parsed as `T` only to check that it is worth caching.
+pub(crate) struct RawResponse<T> {
+    pub(crate) text: String,
+    _response: PhantomData<fn() -> T>,
+}
+
+impl<T: FromLLMResponse> FromLLMResponse for RawResponse<T> {
+    fn from_llm_response(response: String) -> Result<Self> {
+        T::from_llm_response(response.clone())?;
+        Ok(Self { text: response, _response: PhantomData })
+    }
+
+    fn output_format() -> OutputFormat {
+        T::output_format()
+    }
+}
+
+/// Content-addressed cache of LLM responses in a local directory. Entries are keyed on the
+/// provider and model, temperature, system prompt, user prompt and output format, so repeated
+/// requests from a re-run are answered from disk.
+pub struct CachedLLM<T: LLMInterface> {
+    llm: T,
+    dir: PathBuf,
+    mode: CacheMode,
+}
+
+impl<T: LLMInterface> CachedLLM<T> {
+    pub fn new(llm: T, dir: impl Into<PathBuf>) -> Self {
+        Self { llm, dir: dir.into(), mode: CacheMode::default() }
+    }
+
+    pub fn with_mode(mut self, mode: CacheMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    fn cache_key(&self, sys_prompt: &str, user_prompt: &str, output_format: &OutputFormat) -> Result<String> {
+        let key = json!({
+            "provider": self.llm.name(),
+            "temperature": self.llm.temperature(),
+            "system": sys_prompt,
+            "user": user_prompt,
+            "output_format": output_format,
+        });
+        let key = serde_json::to_vec(&key).context("Failed to serialize cache key")?;
+        Ok(format!("{:x}", Sha256::digest(key)))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{}.json", key))
+    }
+
+    async fn read_entry(&self, path: &Path) -> Option<CacheEntry> {
+        let content = tokio::fs::read(path).await.ok()?;
+        match serde_json::from_slice(&content) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Ignoring unreadable cache entry {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Writes through a temporary file so concurrent or interrupted runs never leave a partial entry.
+    async fn write_entry(&self, path: &Path, entry: &CacheEntry) -> Result<()> {
+        let parent = path.parent().context("Cache entry has no parent directory")?;
+        tokio::fs::create_dir_all(parent).await
+            .with_context(|| format!("Failed to create cache directory: {:?}", parent))?;
+        let content = serde_json::to_vec(entry).context("Failed to serialize cache entry")?;
+        let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&temp_path, content).await
+            .with_context(|| format!("Failed to write cache entry: {:?}", temp_path))?;
+        tokio::fs::rename(&temp_path, path).await
+            .with_context(|| format!("Failed to move cache entry into place: {:?}", path))
+    }
+}
+
+impl<T: LLMInterface> LLMInterface for CachedLLM<T> {
+    async fn send_request<R: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<R> {
+        self.send_request_attributed(sys_prompt, user_prompt).await
+            .map(|(response, _)| response)
+    }
+
+    async fn send_request_attributed<R: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<(R, String)> {
+        if self.mode == CacheMode::Bypass {
+            return self.llm.send_request_attributed(sys_prompt, user_prompt).await;
+        }
+
+        let key = self.cache_key(sys_prompt, user_prompt, &R::output_format())?;
+        let path = self.entry_path(&key);
+
+        if matches!(self.mode, CacheMode::ReadWrite | CacheMode::CacheOnly) {
+            if let Some(entry) = self.read_entry(&path).await {
+                debug!("Cache hit for {}", key);
+                let response = R::from_llm_response(entry.response)
+                    .with_context(|| format!("Failed to parse cached response {:?}", path))?;
+                return Ok((response, entry.provider));
+            }
+            if self.mode == CacheMode::CacheOnly {
+                bail!("No cached response for request {} in cache-only mode", key);
+            }
+        }
+
+        debug!("Cache miss for {}", key);
+        let (raw, provider): (RawResponse<R>, String) = self.llm.send_request_attributed(sys_prompt, user_prompt).await?;
+        let entry = CacheEntry { provider, response: raw.text };
+        if let Err(e) = self.write_entry(&path, &entry).await {
+            warn!("Failed to cache response: {:#}", e);
+        }
+        let response = R::from_llm_response(entry.response)?;
+        Ok((response, entry.provider))
+    }
+
+    async fn upload_file(&self, file_path: PathBuf) -> Result<Value> {
+        self.llm.upload_file(file_path).await
+    }
+
+    async fn create_fine_tuning_job(&self, training_file: &str, validation_file: Option<&str>, config: &FineTuneConfig) -> Result<Value> {
+        self.llm.create_fine_tuning_job(training_file, validation_file, config).await
+    }
+
+    async fn train(&self, file_path: PathBuf, validation_file_path: Option<PathBuf>, config: &FineTuneConfig) -> Result<Value> {
+        self.llm.train(file_path, validation_file_path, config).await
+    }
+
+    async fn get_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        self.llm.get_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_events(&self, job_id: &str) -> Result<Value> {
+        self.llm.list_fine_tuning_events(job_id).await
+    }
+
+    async fn list_fine_tuning_jobs(&self, limit: u32) -> Result<Value> {
+        self.llm.list_fine_tuning_jobs(limit).await
+    }
+
+    async fn cancel_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        self.llm.cancel_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_checkpoints(&self, job_id: &str) -> Result<Value> {
+        self.llm.list_fine_tuning_checkpoints(job_id).await
+    }
+
+    fn supports_fine_tuning(&self) -> bool {
+        self.llm.supports_fine_tuning()
+    }
+
+    fn name(&self) -> String {
+        self.llm.name()
+    }
+
+    fn temperature(&self) -> Option<f32> {
+        self.llm.temperature()
+    }
+}
@@ -0,0 +1,179 @@
+use super::llm::{LLMInterface, OutputFormat};
+use crate::core::fine_tuning::FineTuneConfig;
+use super::cache::RawResponse;
+use super::mock::MockProvider;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use crate::utils::llm::FromLLMResponse;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use log::{debug, info};
+
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    key: String,
+    provider: String,
+    system: String,
+    user: String,
+    response: String,
+}
+
+enum CassetteMode {
+    Record(Mutex<BufWriter<File>>),
+    Replay(Mutex<HashMap<String, VecDeque<Interaction>>>),
+}
+
+/// Records the responses of a provider to a JSONL cassette, or replays a cassette without
+/// touching the network, so a run can be reproduced exactly in tests.
+///
+/// Interactions are matched on system prompt, user prompt and output format. A request made more
+/// often than it was recorded gets the recorded responses in order, then the last one again.
+pub struct CassetteProvider<T: LLMInterface> {
+    llm: T,
+    path: PathBuf,
+    mode: CassetteMode,
+}
+
+impl<T: LLMInterface> CassetteProvider<T> {
+    /// Sends requests to `llm` and writes each response to a new cassette at `path`.
+    pub fn record(llm: T, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create cassette: {:?}", path))?;
+        info!("Recording cassette {:?}", path);
+        Ok(Self { llm, path, mode: CassetteMode::Record(Mutex::new(BufWriter::new(file))) })
+    }
+
+    fn record_interaction(&self, writer: &Mutex<BufWriter<File>>, interaction: &Interaction) -> Result<()> {
+        let line = serde_json::to_string(interaction).context("Failed to serialize cassette interaction")?;
+        let mut writer = writer.lock().expect("Cassette writer lock poisoned");
+        writeln!(writer, "{}", line).context("Failed to write cassette interaction")?;
+        writer.flush().context("Failed to flush cassette")
+    }
+}
+
+impl CassetteProvider<MockProvider> {
+    /// Answers requests from the cassette at `path`. An unrecorded request is an error; fine-tuning
+    /// calls go to a `MockProvider`.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let interactions = load_interactions(&path)?;
+        info!("Replaying cassette {:?}", path);
+        Ok(Self { llm: MockProvider::new(), path, mode: CassetteMode::Replay(Mutex::new(interactions)) })
+    }
+
+    /// The mock that receives fine-tuning calls during replay.
+    pub fn mock(&self) -> &MockProvider {
+        &self.llm
+    }
+}
+
+fn load_interactions(path: &Path) -> Result<HashMap<String, VecDeque<Interaction>>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open cassette: {:?}", path))?;
+    let mut interactions: HashMap<String, VecDeque<Interaction>> = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.context("Failed to read cassette line")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let interaction: Interaction = serde_json::from_str(&line)
+            .context("Failed to parse cassette interaction")?;
+        interactions.entry(interaction.key.clone()).or_default().push_back(interaction);
+    }
+    Ok(interactions)
+}
+
+fn interaction_key(sys_prompt: &str, user_prompt: &str, output_format: &OutputFormat) -> Result<String> {
+    let key = json!({"system": sys_prompt, "user": user_prompt, "output_format": output_format});
+    let key = serde_json::to_vec(&key).context("Failed to serialize cassette key")?;
+    Ok(format!("{:x}", Sha256::digest(key)))
+}
+
+impl<T: LLMInterface> LLMInterface for CassetteProvider<T> {
+    async fn send_request<R: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<R> {
+        self.send_request_attributed(sys_prompt, user_prompt).await
+            .map(|(response, _)| response)
+    }
+
+    async fn send_request_attributed<R: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<(R, String)> {
+        let key = interaction_key(sys_prompt, user_prompt, &R::output_format())?;
+        match &self.mode {
+            CassetteMode::Record(writer) => {
+                let (raw, provider): (RawResponse<R>, String) = self.llm.send_request_attributed(sys_prompt, user_prompt).await?;
+                let interaction = Interaction {
+                    key,
+                    provider,
+                    system: sys_prompt.to_string(),
+                    user: user_prompt.to_string(),
+                    response: raw.text,
+                };
+                self.record_interaction(writer, &interaction)?;
+                Ok((R::from_llm_response(interaction.response)?, interaction.provider))
+            },
+            CassetteMode::Replay(interactions) => {
+                let (response, provider) = {
+                    let mut interactions = interactions.lock().expect("Cassette lock poisoned");
+                    let Some(recorded) = interactions.get_mut(&key) else {
+                        bail!("No interaction for request {} in cassette {:?}", key, self.path);
+                    };
+                    let interaction = if recorded.len() > 1 { recorded.pop_front() } else { None };
+                    let interaction = interaction.as_ref().or(recorded.front())
+                        .context("Cassette interaction list is empty")?;
+                    (interaction.response.clone(), interaction.provider.clone())
+                };
+                debug!("Replayed interaction {}", key);
+                Ok((R::from_llm_response(response)?, provider))
+            },
+        }
+    }
+
+    async fn upload_file(&self, file_path: PathBuf) -> Result<Value> {
+        self.llm.upload_file(file_path).await
+    }
+
+    async fn create_fine_tuning_job(&self, training_file: &str, validation_file: Option<&str>, config: &FineTuneConfig) -> Result<Value> {
+        self.llm.create_fine_tuning_job(training_file, validation_file, config).await
+    }
+
+    async fn train(&self, file_path: PathBuf, validation_file_path: Option<PathBuf>, config: &FineTuneConfig) -> Result<Value> {
+        self.llm.train(file_path, validation_file_path, config).await
+    }
+
+    async fn get_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        self.llm.get_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_events(&self, job_id: &str) -> Result<Value> {
+        self.llm.list_fine_tuning_events(job_id).await
+    }
+
+    async fn list_fine_tuning_jobs(&self, limit: u32) -> Result<Value> {
+        self.llm.list_fine_tuning_jobs(limit).await
+    }
+
+    async fn cancel_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        self.llm.cancel_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_checkpoints(&self, job_id: &str) -> Result<Value> {
+        self.llm.list_fine_tuning_checkpoints(job_id).await
+    }
+
+    fn supports_fine_tuning(&self) -> bool {
+        self.llm.supports_fine_tuning()
+    }
+
+    fn name(&self) -> String {
+        self.llm.name()
+    }
+
+    fn temperature(&self) -> Option<f32> {
+        self.llm.temperature()
+    }
+}
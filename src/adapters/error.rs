@@ -0,0 +1,142 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::fmt;
+use tokio::time::Duration;
+use chrono::{DateTime, Utc};
+use std::env;
+use log::error;
+use crate::utils::llm::FromLLMResponse;
+
+/// Classified failure of an LLM provider request, so callers can tell what is worth retrying.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LLMError {
+    /// Network failures, timeouts and provider-side errors that may succeed on retry.
+    Transient(String),
+    /// The provider throttled the request, optionally saying how long to wait.
+    RateLimited { message: String, retry_after: Option<Duration> },
+    /// Missing or rejected credentials, or an account without quota.
+    Auth(String),
+    /// The provider rejected the request itself, e.g. a schema or parameter error.
+    InvalidRequest(String),
+    /// The provider refused to produce output for the content.
+    ContentFiltered(String),
+    /// The model's reply could not be parsed, e.g. JSON cut off at `max_tokens`; a new sample may parse.
+    InvalidResponse(String),
+}
+
+impl LLMError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, LLMError::Transient(_) | LLMError::RateLimited { .. } | LLMError::InvalidResponse(_))
+    }
+
+    /// Short name of the kind of error, used to count failures by cause.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LLMError::Transient(_) => "transient",
+            LLMError::RateLimited { .. } => "rate_limited",
+            LLMError::Auth(_) => "auth",
+            LLMError::InvalidRequest(_) => "invalid_request",
+            LLMError::ContentFiltered(_) => "content_filtered",
+            LLMError::InvalidResponse(_) => "invalid_response",
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            LLMError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Classifies an unsuccessful HTTP response from a provider.
+    pub fn from_response(status: StatusCode, headers: &HeaderMap, body: String) -> Self {
+        let error_code = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|value| {
+                let error = &value["error"];
+                error["code"].as_str().or(error["type"].as_str()).map(|code| code.to_string())
+            })
+            .unwrap_or_default();
+
+        match status {
+            _ if error_code == "insufficient_quota" => LLMError::Auth(body),
+            _ if error_code.contains("content_filter") || error_code.contains("content_policy") => {
+                LLMError::ContentFiltered(body)
+            },
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => LLMError::Auth(body),
+            StatusCode::TOO_MANY_REQUESTS => LLMError::RateLimited {
+                message: body,
+                retry_after: retry_after_from_headers(headers),
+            },
+            StatusCode::REQUEST_TIMEOUT | StatusCode::CONFLICT => LLMError::Transient(body),
+            _ if status.is_server_error() => LLMError::Transient(body),
+            _ => LLMError::InvalidRequest(body),
+        }
+    }
+}
+
+impl fmt::Display for LLMError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LLMError::Transient(message) => write!(f, "Transient provider error: {}", message),
+            LLMError::RateLimited { message, retry_after: Some(retry_after) } => {
+                write!(f, "Rate limited (retry after {:?}): {}", retry_after, message)
+            },
+            LLMError::RateLimited { message, retry_after: None } => write!(f, "Rate limited: {}", message),
+            LLMError::Auth(message) => write!(f, "Authentication error: {}", message),
+            LLMError::InvalidRequest(message) => write!(f, "Invalid request: {}", message),
+            LLMError::ContentFiltered(message) => write!(f, "Content filtered: {}", message),
+            LLMError::InvalidResponse(message) => write!(f, "Invalid model response: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for LLMError {}
+
+/// Reads how long to wait from `retry-after-ms` or `retry-after`, which may hold seconds or an HTTP date.
+pub fn retry_after_from_headers(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+
+    if let Some(milliseconds) = header("retry-after-ms").and_then(|value| value.parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(milliseconds.max(0.0) / 1000.0));
+    }
+    let value = header(RETRY_AFTER.as_str())?;
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(seconds.max(0.0)));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// Finds the provider error classification anywhere in an error's context chain.
+pub fn find_llm_error(error: &anyhow::Error) -> Option<&LLMError> {
+    error.chain().find_map(|cause| cause.downcast_ref::<LLMError>())
+}
+
+/// Reads and classifies the body of an unsuccessful provider response.
+pub async fn error_from_response(response: reqwest::Response) -> LLMError {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let error = match response.text().await {
+        Ok(body) => LLMError::from_response(status, &headers, body),
+        Err(e) => LLMError::Transient(format!("Failed to read error response ({}): {}", status, e)),
+    };
+    error!("LLM API request failed: {}", error);
+    error
+}
+
+/// Parses a model reply, classifying a failure as `InvalidResponse` so it is retried.
+pub fn parse_llm_response<T: FromLLMResponse>(content: String) -> anyhow::Result<T> {
+    T::from_llm_response(content)
+        .map_err(|e| LLMError::InvalidResponse(format!("{:#}", e)).into())
+}
+
+/// Reads an API key, treating an empty variable as unset so no blank credential is sent.
+pub fn api_key_from_env(name: &str) -> anyhow::Result<String> {
+    env::var(name)
+        .ok()
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| LLMError::Auth(format!("{} must be set", name)).into())
+}
@@ -0,0 +1,163 @@
+use super::llm::{LLMProvider, LLMInterface, OutputFormat};
+use crate::core::fine_tuning::FineTuneConfig;
+use super::error::{api_key_from_env, LLMError};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde_json::{json, Map, Value};
+use crate::utils::llm::FromLLMResponse;
+use super::http::{estimate_tokens, request_policy_builders, RequestPolicy};
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+pub const DEFAULT_API_KEY_ENV: &str = "GEMINI_API_KEY";
+const BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Schema keywords Gemini's `responseSchema` understands; anything else is dropped.
+const SCHEMA_KEYWORDS: &[&str] = &[
+    "type", "format", "description", "nullable", "enum", "items", "properties", "required",
+    "minItems", "maxItems", "propertyOrdering",
+];
+
+pub struct Gemini {
+    model: String,
+    temperature: f32,
+    max_tokens: u32,
+    policy: RequestPolicy,
+    api_key_env: String
+}
+
+impl Gemini {
+    pub fn new(model: String, temperature: f32, max_tokens: u32) -> Self {
+        Self { model, temperature, max_tokens, policy: RequestPolicy::default(), api_key_env: DEFAULT_API_KEY_ENV.to_string() }
+    }
+
+    request_policy_builders!();
+
+    pub fn with_api_key_env(mut self, api_key_env: impl Into<String>) -> Self {
+        self.api_key_env = api_key_env.into();
+        self
+    }
+}
+
+/// Translates a JSON Schema such as `INSTRUCTION_RESPONSE_FORMAT`'s into the OpenAPI subset
+/// Gemini accepts as `responseSchema`. A `["string", "null"]` type becomes a nullable string.
+pub fn response_schema(schema: &Value) -> Value {
+    let Some(object) = schema.as_object() else {
+        return schema.clone();
+    };
+
+    let mut translated = Map::new();
+    for (key, value) in object {
+        if !SCHEMA_KEYWORDS.contains(&key.as_str()) {
+            continue;
+        }
+        let value = match key.as_str() {
+            "type" => match value {
+                Value::Array(types) => {
+                    if types.iter().any(|t| t == "null") {
+                        translated.insert("nullable".to_string(), json!(true));
+                    }
+                    match types.iter().find(|t| *t != "null") {
+                        Some(t) => t.clone(),
+                        None => continue,
+                    }
+                },
+                _ => value.clone(),
+            },
+            "items" => response_schema(value),
+            "properties" => Value::Object(value.as_object()
+                .map(|properties| properties.iter()
+                    .map(|(name, property)| (name.clone(), response_schema(property)))
+                    .collect())
+                .unwrap_or_default()),
+            _ => value.clone(),
+        };
+        translated.insert(key.clone(), value);
+    }
+    Value::Object(translated)
+}
+
+fn extract_content(response: &Value) -> Result<String> {
+    if let Some(reason) = response["promptFeedback"]["blockReason"].as_str() {
+        return Err(LLMError::ContentFiltered(format!("Prompt blocked: {}", reason)).into());
+    }
+    let candidate = &response["candidates"][0];
+    if let Some(reason @ ("SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII" | "RECITATION")) = candidate["finishReason"].as_str() {
+        return Err(LLMError::ContentFiltered(format!("Response stopped: {}", reason)).into());
+    }
+    let parts = candidate["content"]["parts"].as_array()
+        .context("Failed to extract content from Gemini API response")?;
+    Ok(parts.iter().filter_map(|part| part["text"].as_str()).collect())
+}
+
+impl LLMProvider for Gemini {
+    fn generate_headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        let api_key = api_key_from_env(&self.api_key_env)?;
+        headers.insert("x-goog-api-key", HeaderValue::from_str(&api_key)
+            .context("Failed to create x-goog-api-key header")?);
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(headers)
+    }
+
+    fn generate_request_body(&self, sys_prompt: &str, user_prompt: &str, output_format: &OutputFormat) -> Result<Value> {
+        let mut body = json!({
+            "systemInstruction": {"parts": [{"text": sys_prompt}]},
+            "contents": [{"role": "user", "parts": [{"text": user_prompt}]}],
+            "generationConfig": {
+                "temperature": self.temperature,
+                "maxOutputTokens": self.max_tokens,
+            },
+        });
+
+        match output_format {
+            OutputFormat::String => {},
+            OutputFormat::Json => {
+                body["generationConfig"]["responseMimeType"] = json!("application/json");
+            },
+            OutputFormat::StrictJson(schema) => {
+                body["generationConfig"]["responseMimeType"] = json!("application/json");
+                body["generationConfig"]["responseSchema"] = response_schema(&schema["json_schema"]["schema"]);
+            },
+        }
+
+        Ok(body)
+    }
+}
+
+impl LLMInterface for Gemini {
+    async fn send_request<T: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<T> {
+        let body = self.generate_request_body(sys_prompt, user_prompt, &T::output_format())?;
+        let client = reqwest::Client::new();
+        self.policy.send(
+            "Gemini",
+            estimate_tokens(sys_prompt, user_prompt, self.max_tokens),
+            || Ok(client.post(format!("{}/models/{}:generateContent", BASE_URL, self.model)).headers(self.generate_headers()?).json(&body)),
+            |response| response["usageMetadata"]["totalTokenCount"].as_u64(),
+            extract_content,
+        ).await
+    }
+
+    fn supports_fine_tuning(&self) -> bool {
+        false
+    }
+
+    fn name(&self) -> String {
+        format!("gemini/{}", self.model)
+    }
+
+    fn temperature(&self) -> Option<f32> {
+        Some(self.temperature)
+    }
+
+    async fn upload_file(&self, _file_path: PathBuf) -> Result<Value> {
+        Err(LLMError::InvalidRequest("Gemini does not support uploading fine-tuning files".to_string()).into())
+    }
+
+    async fn create_fine_tuning_job(&self, _training_file: &str, _validation_file: Option<&str>, _config: &FineTuneConfig) -> Result<Value> {
+        Err(LLMError::InvalidRequest("Gemini does not support fine-tuning jobs".to_string()).into())
+    }
+
+    async fn train(&self, _file_path: PathBuf, _validation_file_path: Option<PathBuf>, _config: &FineTuneConfig) -> Result<Value> {
+        Err(LLMError::InvalidRequest("Gemini does not support fine-tuning".to_string()).into())
+    }
+}
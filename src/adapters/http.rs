@@ -0,0 +1,110 @@
+use super::error::{error_from_response, parse_llm_response, retry_after_from_headers, LLMError};
+use crate::utils::lib::retry;
+use crate::utils::llm::FromLLMResponse;
+use crate::utils::rate_limit::RateLimiter;
+use reqwest::{RequestBuilder, StatusCode};
+use serde_json::Value;
+use tokio::time::Duration;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use log::{info, debug};
+
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Retry and rate limiting settings shared by the HTTP providers.
+pub(crate) struct RequestPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) delay: Duration,
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        Self { max_retries: DEFAULT_MAX_RETRIES, delay: Duration::from_secs(1), rate_limiter: None }
+    }
+}
+
+/// Generates the `with_rate_limiter`, `with_retries` and `with_delay` builders of a provider
+/// that keeps its `RequestPolicy` in a `policy` field.
+macro_rules! request_policy_builders {
+    () => {
+        pub fn with_rate_limiter(mut self, rate_limiter: std::sync::Arc<crate::utils::rate_limit::RateLimiter>) -> Self {
+            self.policy.rate_limiter = Some(rate_limiter);
+            self
+        }
+
+        pub fn with_retries(mut self, max_retries: u32) -> Self {
+            self.policy.max_retries = max_retries;
+            self
+        }
+
+        pub fn with_delay(mut self, delay: tokio::time::Duration) -> Self {
+            self.policy.delay = delay;
+            self
+        }
+    };
+}
+pub(crate) use request_policy_builders;
+
+/// Tokens a request counts against the tokens-per-minute budget: a rough estimate of the
+/// prompt plus `max_tokens`, which providers reserve up front.
+pub(crate) fn estimate_tokens(sys_prompt: &str, user_prompt: &str, max_tokens: u32) -> u32 {
+    ((sys_prompt.len() + user_prompt.len()) / 4) as u32 + max_tokens
+}
+
+impl RequestPolicy {
+    /// Sends the completion request made by `build_request` and parses the model reply, retrying
+    /// retryable failures. `used_tokens` reads the provider's usage report to settle the token
+    /// budget, and `extract_content` pulls the reply text out of the response.
+    pub(crate) async fn send<T, B, U, E>(
+        &self,
+        api: &str,
+        estimated_tokens: u32,
+        build_request: B,
+        used_tokens: U,
+        extract_content: E
+    ) -> Result<T>
+    where
+        T: FromLLMResponse,
+        B: Fn() -> Result<RequestBuilder>,
+        U: Fn(&Value) -> Option<u64>,
+        E: Fn(&Value) -> Result<String>,
+    {
+        retry(self.max_retries, self.delay, || async {
+            let request = build_request()?;
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire(estimated_tokens).await;
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| LLMError::Transient(e.to_string()))
+                .with_context(|| format!("Failed to send request to {} API", api))?;
+
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.observe_headers(response.headers()).await;
+                if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                    rate_limiter.on_rate_limited(retry_after_from_headers(response.headers())).await;
+                }
+            }
+
+            if !response.status().is_success() {
+                return Err(error_from_response(response).await)
+                    .with_context(|| format!("{} API request failed", api));
+            }
+            let response_value = response.json::<Value>().await
+                .map_err(|e| LLMError::Transient(e.to_string()))
+                .with_context(|| format!("Failed to parse {} API response as JSON", api))?;
+            if let Some(rate_limiter) = &self.rate_limiter {
+                if let Some(used_tokens) = used_tokens(&response_value) {
+                    rate_limiter.settle(estimated_tokens, used_tokens as u32).await;
+                }
+                rate_limiter.on_success().await;
+            }
+            let content = extract_content(&response_value)?;
+            info!("{} API request successful", api);
+            debug!("Response: {:?}", response_value);
+            parse_llm_response(content)
+        }).await
+    }
+}
@@ -1,57 +1,463 @@
-use std::fs;
-use text_splitter::TextSplitter;
-use std::path::PathBuf;
+use clap::ValueEnum;
+use text_splitter::{Characters, ChunkConfig, ChunkSizer, MarkdownSplitter, TextSplitter};
+use tiktoken_rs::{cl100k_base, get_bpe_from_model, CoreBPE};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use anyhow::{Context, Result};
-use log::{info, debug};
+use log::{info, debug, warn};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+use sha2::{Digest, Sha256};
+use crate::adapters::readers::{default_readers, DocumentReader, PlainTextReader};
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub text: String,
+    pub page: Option<u32>,
+    pub title: Option<String>,
+}
+
+impl Section {
+    pub fn new(text: String) -> Self {
+        Self { text, page: None, title: None }
+    }
+
+    pub fn with_page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    pub fn with_title(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    Plain,
+    Markdown,
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub source: Option<PathBuf>,
+    pub format: TextFormat,
+    pub sections: Vec<Section>,
+}
+
+impl From<String> for Document {
+    fn from(text: String) -> Self {
+        Self { source: None, format: TextFormat::Plain, sections: vec![Section::new(text)] }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    /// Shared so that pairing a chunk with every prompt doesn't copy its text.
+    pub text: Arc<str>,
+    pub source: Option<PathBuf>,
+    /// Index of the section of the source document the chunk belongs to. Sections are what a reader
+    /// extracts: pages of a PDF, records of a WARC archive, chapters of an EPUB.
+    pub section: usize,
+    /// Byte range of the chunk within its section's extracted text, not within the source file.
+    pub start: usize,
+    pub end: usize,
+    pub page: Option<u32>,
+    pub title: Option<String>,
+    /// Hex-encoded SHA-256 of `text`.
+    pub hash: String,
+    /// Index of the prompt this chunk is paired with, set by `create_chunk_prompt_pairs`.
+    pub prompt_index: Option<usize>,
+}
+
+impl Chunk {
+    pub fn new(text: String) -> Self {
+        let hash = content_hash(&text);
+        let end = text.len();
+        Self { text: Arc::from(text), source: None, section: 0, start: 0, end, page: None, title: None, hash, prompt_index: None }
+    }
+}
+
+pub fn content_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChunkUnit {
+    Chars,
+    Tokens,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Splitter {
+    /// Markdown splitting for Markdown documents, plain text splitting otherwise
+    Auto,
+    Text,
+    Markdown,
+}
+
+pub trait InputAdapter {
+    fn chunk_size(&self) -> usize;
+
+    fn chunk_unit(&self) -> ChunkUnit {
+        ChunkUnit::Chars
+    }
+
+    /// Number of units (see `chunk_unit`) shared between consecutive chunks.
+    fn chunk_overlap(&self) -> usize {
+        0
+    }
+
+    fn splitter(&self) -> Splitter {
+        Splitter::Auto
+    }
+
+    /// Model whose tokenizer measures chunks when `chunk_unit` is `ChunkUnit::Tokens`.
+    fn tokenizer_model(&self) -> Option<&str> {
+        None
+    }
+
+    fn fetch_documents(&self) -> Result<Vec<Document>>;
+
+    /// Yields documents one at a time. The default fetches them all up front;
+    /// override it to keep only the current document in memory.
+    fn documents(&self) -> Result<Box<dyn Iterator<Item = Result<Document>> + '_>> {
+        Ok(Box::new(self.fetch_documents()?.into_iter().map(Ok)))
+    }
+
+    fn chunker(&self) -> Result<DocumentChunker> {
+        info!(
+            "Chunking with size: {} {:?}, overlap: {}, splitter: {:?}",
+            self.chunk_size(), self.chunk_unit(), self.chunk_overlap(), self.splitter()
+        );
+        DocumentChunker::new(
+            self.chunk_size(),
+            self.chunk_unit(),
+            self.chunk_overlap(),
+            self.splitter(),
+            self.tokenizer_model(),
+        )
+    }
+
+    /// Lazily chunks the documents yielded by `documents`.
+    fn chunks(&self) -> Result<Box<dyn Iterator<Item = Result<Chunk>> + '_>> {
+        let chunker = self.chunker()?;
+        let chunks = self.documents()?.flat_map(move |document| match document {
+            Ok(document) => chunker.chunk_document(&document).into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        });
+        Ok(Box::new(chunks))
+    }
+
+    fn fetch_chunks(&self) -> Result<Vec<Chunk>> {
+        let all_chunks = self.chunks()?.collect::<Result<Vec<_>>>()?;
+        info!("Total chunks fetched: {}", all_chunks.len());
+        Ok(all_chunks)
+    }
+}
+
+enum Splitters {
+    Chars(TextSplitter<Characters>, MarkdownSplitter<Characters>),
+    Tokens(Box<(TextSplitter<CoreBPE>, MarkdownSplitter<CoreBPE>)>),
+}
+
+pub struct DocumentChunker {
+    splitters: Splitters,
+    splitter: Splitter,
+}
+
+impl DocumentChunker {
+    pub fn new(
+        size: usize,
+        unit: ChunkUnit,
+        overlap: usize,
+        splitter: Splitter,
+        tokenizer_model: Option<&str>,
+    ) -> Result<Self> {
+        let splitters = match unit {
+            ChunkUnit::Chars => Splitters::Chars(
+                TextSplitter::new(chunk_config(Characters, size, overlap)?),
+                MarkdownSplitter::new(chunk_config(Characters, size, overlap)?),
+            ),
+            ChunkUnit::Tokens => {
+                let tokenizer = tokenizer_for_model(tokenizer_model)?;
+                Splitters::Tokens(Box::new((
+                    TextSplitter::new(chunk_config(tokenizer.clone(), size, overlap)?),
+                    MarkdownSplitter::new(chunk_config(tokenizer, size, overlap)?),
+                )))
+            },
+        };
+        Ok(Self { splitters, splitter })
+    }
+
+    fn split<'text>(&self, text: &'text str, markdown: bool) -> Vec<(usize, &'text str)> {
+        match (&self.splitters, markdown) {
+            (Splitters::Chars(text_splitter, _), false) => text_splitter.chunk_indices(text).collect(),
+            (Splitters::Chars(_, markdown_splitter), true) => markdown_splitter.chunk_indices(text).collect(),
+            (Splitters::Tokens(splitters), false) => splitters.0.chunk_indices(text).collect(),
+            (Splitters::Tokens(splitters), true) => splitters.1.chunk_indices(text).collect(),
+        }
+    }
+
+    pub fn chunk_document(&self, document: &Document) -> Vec<Chunk> {
+        let markdown = match self.splitter {
+            Splitter::Auto => document.format == TextFormat::Markdown,
+            Splitter::Text => false,
+            Splitter::Markdown => true,
+        };
+        let mut chunks = Vec::new();
+        for (index, section) in document.sections.iter().enumerate() {
+            chunks.extend(self.split(&section.text, markdown).into_iter().map(|(start, text)| Chunk {
+                text: Arc::from(text),
+                source: document.source.clone(),
+                section: index,
+                start,
+                end: start + text.len(),
+                page: section.page,
+                title: section.title.clone(),
+                hash: content_hash(text),
+                prompt_index: None,
+            }));
+        }
+        debug!("Split {:?} into {} chunks", document.source, chunks.len());
+        chunks
+    }
+}
+
+fn chunk_config<S: ChunkSizer>(sizer: S, size: usize, overlap: usize) -> Result<ChunkConfig<S>> {
+    ChunkConfig::new(size)
+        .with_sizer(sizer)
+        .with_overlap(overlap)
+        .with_context(|| format!("Invalid chunk overlap {} for chunk size {}", overlap, size))
+}
+
+pub fn tokenizer_for_model(model: Option<&str>) -> Result<CoreBPE> {
+    match model.and_then(|model| get_bpe_from_model(model).ok()) {
+        Some(tokenizer) => Ok(tokenizer),
+        None => {
+            warn!("No tokenizer known for model {:?}, falling back to cl100k_base", model);
+            cl100k_base().context("Failed to load cl100k_base tokenizer")
+        },
+    }
+}
+
+pub fn default_include_patterns() -> Vec<String> {
+    default_readers()
+        .iter()
+        .flat_map(|reader| reader.extensions().iter().map(|extension| format!("*.{}", extension)))
+        .collect()
+}
 
 pub struct FileInputAdapter {
     input_folder: PathBuf,
     chunk_size: usize,
+    recursive: bool,
+    follow_symlinks: bool,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    max_file_size: Option<u64>,
+    chunk_unit: ChunkUnit,
+    chunk_overlap: usize,
+    splitter: Splitter,
+    tokenizer_model: Option<String>,
+    readers: Vec<Box<dyn DocumentReader + Send + Sync>>,
 }
 
 impl FileInputAdapter {
     pub fn new(input_folder: PathBuf, chunk_size: usize) -> Self {
         info!("Creating new FileInputAdapter with input folder: {:?} and chunk size: {}", input_folder, chunk_size);
         Self {
-            input_folder: input_folder,
+            input_folder,
             chunk_size,
+            recursive: false,
+            follow_symlinks: false,
+            include: default_include_patterns(),
+            exclude: Vec::new(),
+            max_file_size: None,
+            chunk_unit: ChunkUnit::Chars,
+            chunk_overlap: 0,
+            splitter: Splitter::Auto,
+            tokenizer_model: None,
+            readers: default_readers(),
         }
     }
 
-    fn read_files_in_folder(&self) -> Result<Vec<String>> {
-        info!("Reading files from folder: {:?}", self.input_folder);
-        let mut contents = Vec::new();
-        for entry in fs::read_dir(&self.input_folder)
-            .with_context(|| format!("Failed to read directory: {:?}", self.input_folder))?
+    pub fn with_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    pub fn with_include(mut self, include: Vec<String>) -> Self {
+        self.include = include;
+        self
+    }
+
+    pub fn with_exclude(mut self, exclude: Vec<String>) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    pub fn with_max_file_size(mut self, max_file_size: Option<u64>) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    pub fn with_chunk_unit(mut self, chunk_unit: ChunkUnit) -> Self {
+        self.chunk_unit = chunk_unit;
+        self
+    }
+
+    pub fn with_chunk_overlap(mut self, chunk_overlap: usize) -> Self {
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+
+    pub fn with_splitter(mut self, splitter: Splitter) -> Self {
+        self.splitter = splitter;
+        self
+    }
+
+    pub fn with_tokenizer_model(mut self, model: String) -> Self {
+        self.tokenizer_model = Some(model);
+        self
+    }
+
+    pub fn with_reader<R: DocumentReader + Send + Sync + 'static>(mut self, reader: R) -> Self {
+        self.readers.push(Box::new(reader));
+        self
+    }
+
+    fn reader_for(&self, path: &Path) -> &(dyn DocumentReader + Send + Sync) {
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        self.readers
+            .iter()
+            .rev()
+            .find(|reader| {
+                extension.as_deref().is_some_and(|extension| reader.extensions().contains(&extension))
+            })
+            .map(|reader| reader.as_ref())
+            .unwrap_or(&PlainTextReader)
+    }
+
+    fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern)
+                .with_context(|| format!("Invalid glob pattern: {}", pattern))?;
+            builder.add(glob);
+        }
+        builder.build().context("Failed to build glob set")
+    }
+
+    fn collect_file_paths(&self) -> Result<Vec<PathBuf>> {
+        let include = Self::build_glob_set(&self.include)?;
+        let exclude = Self::build_glob_set(&self.exclude)?;
+        let max_depth = if self.recursive { usize::MAX } else { 1 };
+
+        let mut paths = Vec::new();
+        for entry in WalkDir::new(&self.input_folder)
+            .follow_links(self.follow_symlinks)
+            .max_depth(max_depth)
+            .sort_by_file_name()
         {
-            let entry = entry.context("Failed to read directory entry")?;
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(extension) = path.extension() {
-                    if extension == "txt" || extension == "md" {
-                        debug!("Reading file: {:?}", path.to_str().unwrap());
-                        let content = fs::read_to_string(&path)
-                            .with_context(|| format!("Failed to read file: {:?}", path))?;
-                        contents.push(content);
-                    }
+            // One unreadable directory or symlink loop should not abort the run, but a missing input folder should
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.depth() == 0 => {
+                    return Err(e).with_context(|| format!("Failed to read directory: {:?}", self.input_folder));
+                },
+                Err(e) => {
+                    warn!("Skipping {:?}: {}", e.path().unwrap_or(&self.input_folder), e);
+                    continue;
+                },
+            };
+            if entry.path_is_symlink() && !self.follow_symlinks {
+                debug!("Skipping symlink: {:?}", entry.path());
+                continue;
+            }
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative_path = entry.path()
+                .strip_prefix(&self.input_folder)
+                .unwrap_or(entry.path());
+            if !include.is_match(relative_path) || exclude.is_match(relative_path) {
+                continue;
+            }
+
+            if let Some(max_file_size) = self.max_file_size {
+                let size = match entry.metadata() {
+                    Ok(metadata) => metadata.len(),
+                    Err(e) => {
+                        warn!("Skipping file {:?}: failed to read metadata: {}", entry.path(), e);
+                        continue;
+                    },
+                };
+                if size > max_file_size {
+                    warn!("Skipping file {:?}: {} bytes exceeds max file size of {} bytes", entry.path(), size, max_file_size);
+                    continue;
                 }
             }
+
+            paths.push(entry.into_path());
         }
-        info!("Read {} files from folder", contents.len());
-        Ok(contents)
-    }
-
-    pub fn fetch_chunks(&self) -> Result<Vec<String>> {
-        info!("Fetching chunks with size: {}", self.chunk_size);
-        let file_contents = self.read_files_in_folder()?;
-        let mut all_chunks = Vec::new();
-        let splitter = TextSplitter::new(self.chunk_size);
-        for (index, content) in file_contents.iter().enumerate() {
-            let chunks: Vec<String> = splitter.chunks(content).map(|s| s.to_string()).collect();
-            debug!("Split content {} into {} chunks", index + 1, chunks.len());
-            all_chunks.extend(chunks);
-        }
-        info!("Total chunks fetched: {}", all_chunks.len());
-        Ok(all_chunks)
+        Ok(paths)
+    }
+
+    fn read_file(&self, path: PathBuf) -> Result<Document> {
+        debug!("Reading file: {:?}", path);
+        let reader = self.reader_for(&path);
+        let sections = reader.read(&path)?;
+        let format = reader.text_format(&path);
+        Ok(Document { source: Some(path), format, sections })
+    }
+}
+
+impl InputAdapter for FileInputAdapter {
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn chunk_unit(&self) -> ChunkUnit {
+        self.chunk_unit
+    }
+
+    fn chunk_overlap(&self) -> usize {
+        self.chunk_overlap
+    }
+
+    fn splitter(&self) -> Splitter {
+        self.splitter
+    }
+
+    fn tokenizer_model(&self) -> Option<&str> {
+        self.tokenizer_model.as_deref()
+    }
+
+    fn fetch_documents(&self) -> Result<Vec<Document>> {
+        self.documents()?.collect()
+    }
+
+    fn documents(&self) -> Result<Box<dyn Iterator<Item = Result<Document>> + '_>> {
+        info!("Reading files from folder: {:?}", self.input_folder);
+        let paths = self.collect_file_paths()?;
+        info!("Found {} files in folder", paths.len());
+        // One unreadable or malformed file should not end a long run
+        Ok(Box::new(paths.into_iter().filter_map(|path| match self.read_file(path.clone()) {
+            Ok(document) => Some(Ok(document)),
+            Err(e) => {
+                warn!("Skipping file {:?}: {:#}", path, e);
+                None
+            },
+        })))
     }
 }
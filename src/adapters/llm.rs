@@ -4,8 +4,48 @@ use serde_json::Value;
 use crate::utils::llm::FromLLMResponse;
 use anyhow::Result;
 use std::path::PathBuf;
+use clap::ValueEnum;
+use super::error::LLMError;
+use crate::core::fine_tuning::FineTuneConfig;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Provider {
+    #[value(name = "openai")]
+    OpenAI,
+    Anthropic,
+    Azure,
+    Gemini,
+}
+
+impl Provider {
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            Provider::OpenAI => "gpt-4o-mini-2024-07-18",
+            Provider::Anthropic => "claude-3-5-haiku-latest",
+            Provider::Azure => "gpt-4o-mini-2024-07-18",
+            Provider::Gemini => "gemini-2.0-flash",
+        }
+    }
+
+    pub fn default_api_key_env(&self) -> &'static str {
+        match self {
+            Provider::OpenAI => "OPENAI_API_KEY",
+            Provider::Anthropic => "ANTHROPIC_API_KEY",
+            Provider::Azure => "AZURE_OPENAI_API_KEY",
+            Provider::Gemini => "GEMINI_API_KEY",
+        }
+    }
+
+    /// Upper bound for generated tokens that the provider's default models accept.
+    pub fn default_max_tokens(&self) -> u32 {
+        match self {
+            Provider::OpenAI | Provider::Azure => 16000,
+            Provider::Anthropic | Provider::Gemini => 8192,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum OutputFormat {
     String,
     Json,
@@ -23,6 +63,10 @@ pub trait LLMProvider: LLMInterface {
 
 }
 
+fn fine_tuning_unsupported(name: &str) -> anyhow::Error {
+    LLMError::InvalidRequest(format!("{} does not support fine-tuning jobs", name)).into()
+}
+
 pub trait LLMInterface {
     fn send_request<T: FromLLMResponse + Send + Sync>(
         &self,
@@ -32,9 +76,72 @@ pub trait LLMInterface {
 
     fn upload_file(&self, file_path: PathBuf) -> impl std::future::Future<Output = Result<Value>>;
 
-    fn create_fine_tuning_job(&self, training_file: &str) -> impl std::future::Future<Output = Result<Value>>;
+    fn create_fine_tuning_job(
+        &self,
+        training_file: &str,
+        validation_file: Option<&str>,
+        config: &FineTuneConfig
+    ) -> impl std::future::Future<Output = Result<Value>>;
+
+    /// Uploads the training file, and the validation file if given, and creates a fine-tuning job
+    /// for them, returning the job.
+    fn train(
+        &self,
+        file_path: PathBuf,
+        validation_file_path: Option<PathBuf>,
+        config: &FineTuneConfig
+    ) -> impl std::future::Future<Output = Result<Value>>;
+
+    fn get_fine_tuning_job(&self, _job_id: &str) -> impl std::future::Future<Output = Result<Value>> {
+        async move { Err(fine_tuning_unsupported(&self.name())) }
+    }
+
+    /// Lists a job's events, newest first, like OpenAI's `fine_tuning/jobs/{id}/events`.
+    fn list_fine_tuning_events(&self, _job_id: &str) -> impl std::future::Future<Output = Result<Value>> {
+        async move { Err(fine_tuning_unsupported(&self.name())) }
+    }
+
+    /// Lists the most recent `limit` jobs, newest first, as a `{"data": [...]}` page.
+    fn list_fine_tuning_jobs(&self, _limit: u32) -> impl std::future::Future<Output = Result<Value>> {
+        async move { Err(fine_tuning_unsupported(&self.name())) }
+    }
+
+    fn cancel_fine_tuning_job(&self, _job_id: &str) -> impl std::future::Future<Output = Result<Value>> {
+        async move { Err(fine_tuning_unsupported(&self.name())) }
+    }
+
+    /// Lists the checkpoints saved during a job, as a `{"data": [...]}` page.
+    fn list_fine_tuning_checkpoints(&self, _job_id: &str) -> impl std::future::Future<Output = Result<Value>> {
+        async move { Err(fine_tuning_unsupported(&self.name())) }
+    }
+
+    /// Whether `upload_file`, `create_fine_tuning_job` and `train` are available from this provider.
+    fn supports_fine_tuning(&self) -> bool {
+        true
+    }
 
-    fn train(&self, file_path: PathBuf) -> impl std::future::Future<Output = Result<()>>;
+    /// Identifies the provider and model in output metadata, e.g. `openai/gpt-4o-mini`.
+    fn name(&self) -> String {
+        std::any::type_name::<Self>().rsplit("::").next().unwrap_or_default().to_string()
+    }
+
+    /// Sampling temperature, if the provider has a single one; part of the response cache key.
+    fn temperature(&self) -> Option<f32> {
+        None
+    }
+
+    /// Like `send_request`, but also returns the name of the provider that produced the response,
+    /// which differs from `name` for providers that route between others.
+    fn send_request_attributed<T: FromLLMResponse + Send + Sync>(
+        &self,
+        sys_prompt: &str,
+        user_prompt: &str
+    ) -> impl std::future::Future<Output = Result<(T, String)>> {
+        async move {
+            let response = self.send_request(sys_prompt, user_prompt).await?;
+            Ok((response, self.name()))
+        }
+    }
 }
 
 
@@ -0,0 +1,179 @@
+use super::llm::{LLMInterface, OutputFormat};
+use crate::core::fine_tuning::FineTuneConfig;
+use super::error::LLMError;
+use serde_json::{json, Map, Value};
+use crate::utils::llm::FromLLMResponse;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::path::PathBuf;
+use anyhow::Result;
+
+/// A request received by a `MockProvider`.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub system: String,
+    pub user: String,
+    pub output_format: OutputFormat,
+}
+
+type MockHandler = Box<dyn Fn(&MockRequest) -> Result<String> + Send + Sync>;
+
+/// Offline `LLMInterface` for tests. Responses come from, in order of precedence, the queue filled
+/// with `with_response`/`with_error`, the handler set with `with_handler`, or a placeholder built from
+/// the requested output format, so a whole run works without configuring anything.
+pub struct MockProvider {
+    name: String,
+    queue: Mutex<VecDeque<Result<String, LLMError>>>,
+    handler: Option<MockHandler>,
+    requests: Mutex<Vec<MockRequest>>,
+    trained_files: Mutex<Vec<PathBuf>>,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self {
+            name: "mock".to_string(),
+            queue: Mutex::new(VecDeque::new()),
+            handler: None,
+            requests: Mutex::new(Vec::new()),
+            trained_files: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Queues a raw response text, returned by the next request that finds the queue non-empty.
+    pub fn with_response(self, response: impl Into<String>) -> Self {
+        self.queue.lock().expect("Mock queue lock poisoned").push_back(Ok(response.into()));
+        self
+    }
+
+    /// Queues an error, e.g. `LLMError::RateLimited`, to exercise retries and failover.
+    pub fn with_error(self, error: LLMError) -> Self {
+        self.queue.lock().expect("Mock queue lock poisoned").push_back(Err(error));
+        self
+    }
+
+    pub fn with_handler(mut self, handler: impl Fn(&MockRequest) -> Result<String> + Send + Sync + 'static) -> Self {
+        self.handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Requests received so far, in the order they arrived.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().expect("Mock requests lock poisoned").clone()
+    }
+
+    /// Files passed to `upload_file` or `train`, validation files included.
+    pub fn trained_files(&self) -> Vec<PathBuf> {
+        self.trained_files.lock().expect("Mock trained files lock poisoned").clone()
+    }
+
+    fn respond(&self, request: &MockRequest) -> Result<String> {
+        if let Some(queued) = self.queue.lock().expect("Mock queue lock poisoned").pop_front() {
+            return queued.map_err(Into::into);
+        }
+        match &self.handler {
+            Some(handler) => handler(request),
+            None => Ok(placeholder_response(request)),
+        }
+    }
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn placeholder_response(request: &MockRequest) -> String {
+    match &request.output_format {
+        OutputFormat::String => "mock response".to_string(),
+        OutputFormat::Json => "{}".to_string(),
+        OutputFormat::StrictJson(schema) => placeholder_value(&schema["json_schema"]["schema"], "response").to_string(),
+    }
+}
+
+/// A value matching the JSON schema: strings name the field they fill, arrays hold one item.
+fn placeholder_value(schema: &Value, field: &str) -> Value {
+    match schema["type"].as_str() {
+        Some("object") => {
+            let properties = schema["properties"].as_object().cloned().unwrap_or_default();
+            let object: Map<String, Value> = properties.iter()
+                .map(|(name, property)| (name.clone(), placeholder_value(property, name)))
+                .collect();
+            Value::Object(object)
+        },
+        Some("array") => json!([placeholder_value(&schema["items"], field)]),
+        Some("integer") | Some("number") => json!(0),
+        Some("boolean") => json!(false),
+        Some("null") => Value::Null,
+        _ => json!(format!("mock {}", field)),
+    }
+}
+
+impl LLMInterface for MockProvider {
+    async fn send_request<T: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<T> {
+        let request = MockRequest {
+            system: sys_prompt.to_string(),
+            user: user_prompt.to_string(),
+            output_format: T::output_format(),
+        };
+        self.requests.lock().expect("Mock requests lock poisoned").push(request.clone());
+        T::from_llm_response(self.respond(&request)?)
+    }
+
+    async fn upload_file(&self, file_path: PathBuf) -> Result<Value> {
+        let mut trained_files = self.trained_files.lock().expect("Mock trained files lock poisoned");
+        trained_files.push(file_path);
+        Ok(json!({"id": format!("file-mock-{}", trained_files.len()), "status": "processed"}))
+    }
+
+    /// Returns the job as OpenAI would, with the config applied, so tests can check what was sent.
+    async fn create_fine_tuning_job(&self, training_file: &str, validation_file: Option<&str>, config: &FineTuneConfig) -> Result<Value> {
+        let mut job = json!({"id": "ftjob-mock", "training_file": training_file, "validation_file": validation_file, "status": "succeeded"});
+        config.apply(&mut job, false);
+        Ok(job)
+    }
+
+    async fn train(&self, file_path: PathBuf, validation_file_path: Option<PathBuf>, config: &FineTuneConfig) -> Result<Value> {
+        let upload_response = self.upload_file(file_path).await?;
+        let validation_file_id = match validation_file_path {
+            Some(validation_file_path) => self.upload_file(validation_file_path).await?["id"].as_str().map(str::to_string),
+            None => None,
+        };
+        self.create_fine_tuning_job(upload_response["id"].as_str().unwrap_or_default(), validation_file_id.as_deref(), config).await
+    }
+
+    /// Mock jobs finish immediately.
+    async fn get_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        Ok(json!({"id": job_id, "status": "succeeded", "fine_tuned_model": format!("ft:{}", self.name)}))
+    }
+
+    async fn list_fine_tuning_events(&self, _job_id: &str) -> Result<Value> {
+        Ok(json!({"data": [
+            {"id": "ftevent-mock-2", "created_at": 0, "level": "info", "message": "The job has successfully completed", "type": "message"},
+            {"id": "ftevent-mock-1", "created_at": 0, "level": "info", "message": "Step 1/1: training loss=0.0000", "type": "metrics",
+             "data": {"step": 1, "total_steps": 1, "train_loss": 0.0}},
+        ]}))
+    }
+
+    async fn list_fine_tuning_jobs(&self, _limit: u32) -> Result<Value> {
+        Ok(json!({"data": [self.get_fine_tuning_job("ftjob-mock").await?]}))
+    }
+
+    async fn cancel_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        Ok(json!({"id": job_id, "status": "cancelled"}))
+    }
+
+    async fn list_fine_tuning_checkpoints(&self, _job_id: &str) -> Result<Value> {
+        Ok(json!({"data": []}))
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
@@ -1,4 +1,14 @@
 pub mod openai;
+pub mod anthropic;
+pub mod azure;
+pub mod gemini;
+pub mod http;
+pub mod router;
+pub mod cache;
+pub mod mock;
+pub mod cassette;
 pub mod llm;
+pub mod error;
 pub mod input;
 pub mod output;
+pub mod readers;
@@ -1,46 +1,232 @@
 use super::llm::{LLMProvider,LLMInterface, OutputFormat};
+use crate::core::fine_tuning::FineTuneConfig;
+use super::error::{api_key_from_env, error_from_response, LLMError};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, AUTHORIZATION};
+use reqwest::RequestBuilder;
 use serde_json::{json, Value};
-use std::env;
 use crate::utils::llm::FromLLMResponse;
-use tokio::time::Duration;
-use crate::utils::lib::*;
-use anyhow::{Context, Result};
-use std::path::PathBuf;
+use super::http::{estimate_tokens, request_policy_builders, RequestPolicy};
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
 use reqwest::multipart::{Form, Part};
-use log::{info, debug, error};
-
+use tokio::time::{sleep, Duration, Instant};
+use log::{info, debug};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+pub const DEFAULT_API_KEY_ENV: &str = "OPENAI_API_KEY";
+pub const DEFAULT_FILE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Where requests are sent and how they are authorized. Azure OpenAI serves the same API under
+/// its own URL scheme, with chat requests going to a deployment of the model.
+pub(crate) enum Endpoint {
+    OpenAI { base_url: String },
+    Azure { endpoint: String, deployment: String, api_version: String },
+}
 
 pub struct OpenAI {
     model: String,
     temperature: f32,
     max_tokens: u32,
-    max_retries: u32,
-    delay: Duration
+    policy: RequestPolicy,
+    endpoint: Endpoint,
+    api_key_env: String,
+    extra_headers: HeaderMap,
+    file_timeout: Duration
 }
 
 impl OpenAI {
     pub fn new(model: String, temperature: f32, max_tokens: u32) -> Self {
-        Self { model, temperature, max_tokens, max_retries: 3, delay: Duration::from_secs(1) }
+        Self {
+            model,
+            temperature,
+            max_tokens,
+            policy: RequestPolicy::default(),
+            endpoint: Endpoint::OpenAI { base_url: DEFAULT_BASE_URL.to_string() },
+            api_key_env: DEFAULT_API_KEY_ENV.to_string(),
+            extra_headers: HeaderMap::new(),
+            file_timeout: DEFAULT_FILE_TIMEOUT,
+        }
+    }
+
+    /// Points the adapter at an OpenAI-compatible server such as Ollama, vLLM, llama.cpp or LM Studio,
+    /// e.g. `http://localhost:11434/v1`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.endpoint = Endpoint::OpenAI { base_url: base_url.into().trim_end_matches('/').to_string() };
+        self
+    }
+
+    pub(crate) fn with_endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// How long to wait for Azure to process an uploaded file before giving up on training.
+    pub(crate) fn with_file_timeout(mut self, file_timeout: Duration) -> Self {
+        self.file_timeout = file_timeout;
+        self
+    }
+
+    /// Sets the API version of an Azure endpoint; OpenAI endpoints are unversioned.
+    pub(crate) fn with_api_version(mut self, version: impl Into<String>) -> Self {
+        if let Endpoint::Azure { api_version, .. } = &mut self.endpoint {
+            *api_version = version.into();
+        }
+        self
     }
 
-    pub fn with_retries(mut self, max_retries: u32) -> Self {
-        self.max_retries = max_retries;
+    request_policy_builders!();
+
+    pub fn with_api_key_env(mut self, api_key_env: impl Into<String>) -> Self {
+        self.api_key_env = api_key_env.into();
         self
     }
 
-    pub fn with_delay(mut self, delay: Duration) -> Self {
-        self.delay = delay;
+    /// Headers sent with every request in addition to the authorization and content type headers.
+    pub fn with_extra_headers(mut self, extra_headers: HeaderMap) -> Self {
+        self.extra_headers = extra_headers;
         self
     }
+
+    fn api(&self) -> &'static str {
+        match self.endpoint {
+            Endpoint::OpenAI { .. } => "OpenAI",
+            Endpoint::Azure { .. } => "Azure OpenAI",
+        }
+    }
+
+    fn is_azure(&self) -> bool {
+        matches!(self.endpoint, Endpoint::Azure { .. })
+    }
+
+    fn url(&self, path: &str) -> String {
+        match &self.endpoint {
+            Endpoint::OpenAI { base_url } => format!("{}/{}", base_url, path),
+            Endpoint::Azure { endpoint, api_version, .. } => {
+                format!("{}/openai/{}?api-version={}", endpoint, path, api_version)
+            },
+        }
+    }
+
+    fn chat_url(&self) -> String {
+        match &self.endpoint {
+            Endpoint::OpenAI { .. } => self.url("chat/completions"),
+            Endpoint::Azure { deployment, .. } => self.url(&format!("deployments/{}/chat/completions", deployment)),
+        }
+    }
+
+    /// Local servers usually ignore the API key, so it is only required when talking to OpenAI itself.
+    fn api_key(&self) -> Result<Option<String>> {
+        match (api_key_from_env(&self.api_key_env), &self.endpoint) {
+            (Ok(api_key), _) => Ok(Some(api_key)),
+            (Err(_), Endpoint::OpenAI { base_url }) if base_url != DEFAULT_BASE_URL => {
+                debug!("{} is not set, sending requests to {} without an API key", self.api_key_env, base_url);
+                Ok(None)
+            },
+            (Err(e), _) => Err(e),
+        }
+    }
+
+    /// Sends an authorized request that takes no body and parses the JSON response.
+    async fn execute(&self, request: RequestBuilder) -> Result<Value> {
+        let response = request
+            .headers(self.auth_headers()?)
+            .send()
+            .await
+            .map_err(|e| LLMError::Transient(e.to_string()))
+            .with_context(|| format!("Failed to send request to {} API", self.api()))?;
+        if response.status().is_success() {
+            let response_json: Value = response.json().await
+                .with_context(|| format!("Failed to parse {} API response as JSON", self.api()))?;
+            debug!("Response: {:?}", response_json);
+            Ok(response_json)
+        } else {
+            Err(error_from_response(response).await)
+                .with_context(|| format!("{} API request failed", self.api()))
+        }
+    }
+
+    fn auth_headers(&self) -> Result<HeaderMap> {
+        let mut headers = self.extra_headers.clone();
+        match (self.api_key()?, &self.endpoint) {
+            (Some(api_key), Endpoint::OpenAI { .. }) => {
+                headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", api_key))
+                    .context("Failed to create Authorization header")?);
+            },
+            (Some(api_key), Endpoint::Azure { .. }) => {
+                headers.insert("api-key", HeaderValue::from_str(&api_key)
+                    .context("Failed to create api-key header")?);
+            },
+            (None, _) => {},
+        }
+        Ok(headers)
+    }
+
+    /// Azure rejects fine-tuning jobs for files it has not finished importing, so wait until the
+    /// upload is processed, giving up after `file_timeout`.
+    async fn wait_for_file(&self, file_id: &str) -> Result<()> {
+        let deadline = Instant::now() + self.file_timeout;
+        loop {
+            let client = reqwest::Client::new();
+            let file = self.execute(client.get(self.url(&format!("files/{}", file_id)))).await
+                .context("Failed to get file status")?;
+            match file["status"].as_str() {
+                Some("processed") => return Ok(()),
+                Some("error") | Some("deleted") => bail!("{} could not process file {}: {}", self.api(), file_id, file["status_details"]),
+                status => debug!("File {} status: {:?}", file_id, status),
+            }
+            if Instant::now() >= deadline {
+                bail!(
+                    "{} did not process file {} within {:?}; its status is {}",
+                    self.api(), file_id, self.file_timeout, file["status"]
+                );
+            }
+            sleep(FILE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Extracts the message content from a chat completion, shared with OpenAI-compatible providers.
+pub(crate) fn chat_completion_content(response_value: &Value) -> Result<String> {
+    let choice = &response_value["choices"][0];
+    if choice["finish_reason"] == "content_filter" {
+        return Err(LLMError::ContentFiltered("Response stopped by content filter".to_string()).into());
+    }
+    if let Some(refusal) = choice["message"]["refusal"].as_str() {
+        return Err(LLMError::ContentFiltered(refusal.to_string()).into());
+    }
+    let content = choice["message"]["content"]
+        .as_str()
+        .context("Failed to extract content from OpenAI API response")?
+        .to_string();
+    Ok(content)
+}
+
+pub(crate) async fn fine_tuning_file_form(file_path: &Path) -> Result<Form> {
+    let purpose = "fine-tune";
+
+    let content = tokio::fs::read(file_path)
+        .await
+        .context("Failed to read file")?;
+
+    let file_name = file_path.file_name()
+        .and_then(|n| n.to_str())
+        .context("Failed to get file name")?
+        .to_string();
+
+    let part = Part::bytes(content)
+        .file_name(file_name)
+        .mime_str("application/json")
+        .context("Failed to set MIME type")?;
+
+    Ok(Form::new()
+        .part("file", part)
+        .text("purpose", purpose.to_string()))
 }
- 
+
 impl LLMProvider for OpenAI {
     fn generate_headers(&self) -> Result<HeaderMap> {
-        let mut headers = HeaderMap::new();
-        let api_key = env::var("OPENAI_API_KEY").context("OPENAI_API_KEY must be set")?;
-        headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", api_key))
-            .context("Failed to create Authorization header")?);
+        let mut headers = self.auth_headers()?;
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         Ok(headers)
     }
@@ -52,11 +238,14 @@ impl LLMProvider for OpenAI {
         ];
 
         let mut body = json!({
-            "model": self.model,
             "messages": messages,
             "temperature": self.temperature,
             "max_tokens": self.max_tokens,
         });
+        // On Azure the deployment in the URL selects the model
+        if !self.is_azure() {
+            body["model"] = json!(self.model);
+        }
 
         match output_format {
             OutputFormat::String => {},
@@ -70,69 +259,40 @@ impl LLMProvider for OpenAI {
 
         Ok(body)
     }
-
-    
 }
 
 impl LLMInterface for OpenAI {
     async fn send_request<T: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<T> {
-        retry(self.max_retries, self.delay, || async {
-            let client = reqwest::Client::new();
-            let headers = self.generate_headers()?;
-            let output_format = T::output_format();
-            let body = self.generate_request_body(sys_prompt, user_prompt, &output_format)?;
-            let response = client.post("https://api.openai.com/v1/chat/completions")
-                .headers(headers)
-                .json(&body)
-                .send()
-                .await
-                .context("Failed to send request to OpenAI API")?;
-
-            if response.status().is_success() {
-                let response_value = response.json::<Value>().await
-                    .context("Failed to parse OpenAI API response as JSON")?;
-                let content = response_value["choices"][0]["message"]["content"]
-                    .as_str()
-                    .context("Failed to extract content from OpenAI API response")?
-                    .to_string();
-                info!("OpenAI API request successful");
-                debug!("Response: {:?}", response_value);
-                T::from_llm_response(content)
-            } else {
-                let error_text = response.text().await
-                    .context("Failed to get error text from OpenAI API")?;
-                error!("OpenAI API request failed: {}", error_text);
-                anyhow::bail!("OpenAI API request failed: {}", error_text)
-            }
-        }).await
-    }
-
-    async fn upload_file(&self, file_path: PathBuf) -> Result<Value> {
-        let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
+        let body = self.generate_request_body(sys_prompt, user_prompt, &T::output_format())?;
         let client = reqwest::Client::new();
+        self.policy.send(
+            self.api(),
+            estimate_tokens(sys_prompt, user_prompt, self.max_tokens),
+            || Ok(client.post(self.chat_url()).headers(self.generate_headers()?).json(&body)),
+            |response| response["usage"]["total_tokens"].as_u64(),
+            chat_completion_content,
+        ).await
+    }
 
-        let purpose = "fine-tune";
-
-        let content = tokio::fs::read(&file_path)
-            .await
-            .context("Failed to read file")?;
+    fn name(&self) -> String {
+        match &self.endpoint {
+            Endpoint::OpenAI { .. } => format!("openai/{}", self.model),
+            Endpoint::Azure { deployment, .. } => format!("azure/{}", deployment),
+        }
+    }
 
-        let file_name = file_path.file_name()
-            .and_then(|n| n.to_str())
-            .context("Failed to get file name")?
-            .to_string();
+    fn temperature(&self) -> Option<f32> {
+        Some(self.temperature)
+    }
 
-        let part = Part::bytes(content)
-            .file_name(file_name)
-            .mime_str("application/json")
-            .context("Failed to set MIME type")?;
+    async fn upload_file(&self, file_path: PathBuf) -> Result<Value> {
+        let headers = self.auth_headers()?;
+        let client = reqwest::Client::new();
 
-        let form = Form::new()
-            .part("file", part)
-            .text("purpose", purpose.to_string());
+        let form = fine_tuning_file_form(&file_path).await?;
 
-        let response = client.post("https://api.openai.com/v1/files")
-            .header(AUTHORIZATION, format!("Bearer {}", api_key))
+        let response = client.post(self.url("files"))
+            .headers(headers)
             .multipart(form)
             .send()
             .await
@@ -145,25 +305,26 @@ impl LLMInterface for OpenAI {
             debug!("Response: {:?}", response_json);
             Ok(response_json)
         } else {
-            let error_text = response.text().await
-                .context("Failed to get error text from upload response")?;
-            error!("File upload failed: {}", error_text);
-            Err(anyhow::anyhow!("File upload failed: {}", error_text))
+            Err(error_from_response(response).await)
+                .context("File upload failed")
         }
     }
 
-     async fn create_fine_tuning_job(&self, training_file: &str) -> Result<Value> {
-        let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
+     async fn create_fine_tuning_job(&self, training_file: &str, validation_file: Option<&str>, config: &FineTuneConfig) -> Result<Value> {
+        let headers = self.generate_headers()?;
         let client = reqwest::Client::new();
 
-        let body = json!({
+        let mut body = json!({
             "training_file": training_file,
             "model": self.model,
         });
+        if let Some(validation_file) = validation_file {
+            body["validation_file"] = json!(validation_file);
+        }
+        config.apply(&mut body, self.is_azure());
 
-        let response = client.post("https://api.openai.com/v1/fine_tuning/jobs")
-            .header(AUTHORIZATION, format!("Bearer {}", api_key))
-            .header(CONTENT_TYPE, "application/json")
+        let response = client.post(self.url("fine_tuning/jobs"))
+            .headers(headers)
             .json(&body)
             .send()
             .await
@@ -176,25 +337,43 @@ impl LLMInterface for OpenAI {
             debug!("Response: {:?}", response_json);
             Ok(response_json)
         } else {
-            let error_text = response.text().await
-                .context("Failed to get error text from fine-tuning job response")?;
-            error!("Fine-tuning job creation failed: {}", error_text);
-            Err(anyhow::anyhow!("Fine-tuning job creation failed: {}", error_text))
+            Err(error_from_response(response).await)
+                .context("Fine-tuning job creation failed")
         }
     }
 
-    async fn train(
-        &self,
-        file_path: PathBuf
-    ) -> Result<()> {
+    async fn train(&self, file_path: PathBuf, validation_file_path: Option<PathBuf>, config: &FineTuneConfig) -> Result<Value> {
         let upload_response = self.upload_file(file_path).await
             .context("Failed to upload training file")?;
         
         let training_file_id = upload_response["id"].as_str()
             .context("Failed to get training file ID from upload response")?;
-     
+
+        let validation_upload_response = match validation_file_path {
+            Some(validation_file_path) => Some(self.upload_file(validation_file_path).await
+                .context("Failed to upload validation file")?),
+            None => None,
+        };
+        let validation_file_id = match &validation_upload_response {
+            Some(response) => Some(response["id"].as_str()
+                .context("Failed to get validation file ID from upload response")?),
+            None => None,
+        };
+
+        if self.is_azure() {
+            println!("Waiting for Azure to process training file {}", training_file_id);
+            self.wait_for_file(training_file_id).await
+                .context("Failed to wait for training file to be processed")?;
+            if let Some(validation_file_id) = validation_file_id {
+                self.wait_for_file(validation_file_id).await
+                    .context("Failed to wait for validation file to be processed")?;
+            }
+        }
+
         let fine_tuning_response = self.create_fine_tuning_job(
             training_file_id,
+            validation_file_id,
+            config,
         ).await
         .context("Failed to create fine-tuning job")?;
 
@@ -202,15 +381,39 @@ impl LLMInterface for OpenAI {
             .context("Failed to get status from fine-tuning job response")?
             .to_string();
 
-        println!("Fine-tuning job created. Status: {}", status);
+        println!("Fine-tuning job {} created. Status: {}", fine_tuning_response["id"].as_str().unwrap_or_default(), status);
         debug!("Response: {:?}", fine_tuning_response);
-        Ok(())
+        Ok(fine_tuning_response)
     }
 
-    
+    async fn get_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        let client = reqwest::Client::new();
+        self.execute(client.get(self.url(&format!("fine_tuning/jobs/{}", job_id)))).await
+            .context("Failed to get fine-tuning job")
+    }
 
-    
+    async fn list_fine_tuning_events(&self, job_id: &str) -> Result<Value> {
+        let client = reqwest::Client::new();
+        self.execute(client.get(self.url(&format!("fine_tuning/jobs/{}/events", job_id))).query(&[("limit", 100)])).await
+            .context("Failed to list fine-tuning job events")
+    }
 
-}
+    async fn list_fine_tuning_jobs(&self, limit: u32) -> Result<Value> {
+        let client = reqwest::Client::new();
+        self.execute(client.get(self.url("fine_tuning/jobs")).query(&[("limit", limit)])).await
+            .context("Failed to list fine-tuning jobs")
+    }
 
+    async fn cancel_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        let client = reqwest::Client::new();
+        self.execute(client.post(self.url(&format!("fine_tuning/jobs/{}/cancel", job_id)))).await
+            .context("Failed to cancel fine-tuning job")
+    }
+
+    async fn list_fine_tuning_checkpoints(&self, job_id: &str) -> Result<Value> {
+        let client = reqwest::Client::new();
+        self.execute(client.get(self.url(&format!("fine_tuning/jobs/{}/checkpoints", job_id))).query(&[("limit", 100)])).await
+            .context("Failed to list fine-tuning checkpoints")
+    }
+}
 
@@ -2,8 +2,10 @@ use anyhow::{Context, Result};
 use serde::Serialize;
 use std::fs::File;
 use std::sync::Arc;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use tokio::sync::Mutex;
+use std::path::Path;
+use crate::adapters::input::Chunk;
 use crate::core::learn::Instruction;
 use log::{info, debug};
 
@@ -18,8 +20,44 @@ struct JsonLine {
     messages: Vec<Message>,
 }
 
+#[derive(Serialize)]
+struct MetadataLine<'a> {
+    source: Option<&'a Path>,
+    section: usize,
+    start: usize,
+    end: usize,
+    page: Option<u32>,
+    title: Option<&'a str>,
+    hash: &'a str,
+    prompt_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<&'a str>,
+}
+
+impl<'a> From<&'a Chunk> for MetadataLine<'a> {
+    fn from(chunk: &'a Chunk) -> Self {
+        Self {
+            source: chunk.source.as_deref(),
+            section: chunk.section,
+            start: chunk.start,
+            end: chunk.end,
+            page: chunk.page,
+            title: chunk.title.as_deref(),
+            hash: &chunk.hash,
+            prompt_index: chunk.prompt_index,
+            provider: None,
+        }
+    }
+}
+
+/// Writes the instruction as a JSONL line. When a metadata writer is given, the chunk's provenance
+/// and the provider that produced the instruction are written to it while the output lock is held,
+/// so line N of the sidecar describes line N of the output.
 pub async fn output_jsonl(
     writer: &Arc<Mutex<std::io::BufWriter<File>>>, 
+    metadata_writer: Option<&Arc<Mutex<std::io::BufWriter<File>>>>,
+    chunk: &Chunk,
+    provider: &str,
     instruction: Instruction
 ) -> Result<()> {
     info!("Starting to output JSONL for instruction");
@@ -46,7 +84,41 @@ pub async fn output_jsonl(
     let mut writer = writer.lock().await;
     writeln!(writer, "{}", json)
         .context("Failed to write JSON line to file")?;
+
+    if let Some(metadata_writer) = metadata_writer {
+        let metadata = MetadataLine { provider: Some(provider), ..MetadataLine::from(chunk) };
+        let metadata = serde_json::to_string(&metadata)
+            .context("Failed to serialize chunk metadata to string")?;
+        let mut metadata_writer = metadata_writer.lock().await;
+        writeln!(metadata_writer, "{}", metadata)
+            .context("Failed to write chunk metadata to file")?;
+    }
     
     info!("Successfully wrote JSON line to file");
     Ok(())
-}
\ No newline at end of file
+}
+
+#[derive(Serialize)]
+struct RejectLine<'a> {
+    #[serde(flatten)]
+    metadata: MetadataLine<'a>,
+    prompt: &'a str,
+    chunk: &'a str,
+    error: &'a str,
+}
+
+/// Appends a failed chunk-prompt pair as a JSONL line so it can be inspected or retried. The line
+/// is flushed at once, so rejects survive a crash later in the run.
+pub fn output_reject(writer: &mut BufWriter<File>, chunk: &Chunk, prompt: &str, error: &str) -> Result<()> {
+    let line = RejectLine {
+        metadata: MetadataLine::from(chunk),
+        prompt,
+        chunk: &chunk.text,
+        error,
+    };
+    let json = serde_json::to_string(&line)
+        .context("Failed to serialize rejected pair to string")?;
+    writeln!(writer, "{}", json)
+        .context("Failed to write rejected pair to file")?;
+    writer.flush().context("Failed to flush rejects file")
+}
@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+use anyhow::{Context, Result};
+use log::debug;
+use roxmltree::{Document as XmlDocument, Node};
+use zip::ZipArchive;
+use crate::adapters::input::Section;
+use super::{read_zip_entry, DocumentReader};
+
+const WORD_NAMESPACE: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const MARKUP_COMPATIBILITY_NAMESPACE: &str = "http://schemas.openxmlformats.org/markup-compatibility/2006";
+/// Names of the built-in styles that start a section. Style names stay English in localized
+/// documents; only the style IDs are translated, e.g. `berschrift1` in German.
+const SECTION_STYLE_NAMES: &[&str] = &["title", "heading 1", "heading 2"];
+/// Style IDs of the section styles in English documents, used when a document has no styles part.
+const SECTION_STYLE_IDS: &[&str] = &["Title", "Heading1", "Heading2"];
+
+/// Reads Word documents, starting a new section at every title or top-level heading.
+pub struct DocxReader;
+
+impl DocumentReader for DocxReader {
+    fn extensions(&self) -> &[&str] {
+        &["docx"]
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<Section>> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open file: {:?}", path))?;
+        let mut archive = ZipArchive::new(file)
+            .with_context(|| format!("Failed to open DOCX archive: {:?}", path))?;
+        let document = read_zip_entry(&mut archive, "word/document.xml")?;
+        let xml = XmlDocument::parse(&document)
+            .with_context(|| format!("Failed to parse DOCX document: {:?}", path))?;
+        let heading_styles = match read_zip_entry(&mut archive, "word/styles.xml") {
+            Ok(styles) => section_heading_styles(&styles)
+                .with_context(|| format!("Failed to parse DOCX styles: {:?}", path))?,
+            Err(_) => SECTION_STYLE_IDS.iter().map(|id| id.to_string()).collect(),
+        };
+
+        let mut sections = Vec::new();
+        let mut current = Section::new(String::new());
+        // Paragraphs inside text boxes are part of the paragraph that anchors the text box
+        let paragraphs = xml.descendants()
+            .filter(|node| is_word_element(node, "p"))
+            .filter(|node| !node.ancestors().skip(1).any(|ancestor| is_word_element(&ancestor, "p")));
+        for paragraph in paragraphs {
+            let text = paragraph_text(paragraph);
+            if is_section_heading(paragraph, &heading_styles) && !text.trim().is_empty() {
+                push_section(&mut sections, current);
+                current = Section::new(String::new()).with_title(text.trim().to_string());
+            }
+            if !current.text.is_empty() {
+                current.text.push('\n');
+            }
+            current.text.push_str(&text);
+        }
+        push_section(&mut sections, current);
+        debug!("Read {} sections from DOCX: {:?}", sections.len(), path);
+        Ok(sections)
+    }
+}
+
+fn push_section(sections: &mut Vec<Section>, mut section: Section) {
+    section.text = section.text.trim().to_string();
+    if !section.text.is_empty() {
+        sections.push(section);
+    }
+}
+
+fn is_word_element(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name && node.tag_name().namespace() == Some(WORD_NAMESPACE)
+}
+
+/// Text of a paragraph, with nested text box paragraphs on lines of their own. Fallback content for
+/// older readers repeats the preferred content, so it is skipped.
+fn paragraph_text(paragraph: Node) -> String {
+    let mut text = String::new();
+    let nodes = paragraph.descendants()
+        .filter(|node| !node.ancestors().any(|ancestor| is_fallback(&ancestor)));
+    for node in nodes {
+        if is_word_element(&node, "p") && node != paragraph {
+            text.push('\n');
+        } else if is_word_element(&node, "t") {
+            text.push_str(node.text().unwrap_or_default());
+        } else if is_word_element(&node, "tab") {
+            text.push('\t');
+        } else if is_word_element(&node, "br") || is_word_element(&node, "cr") {
+            text.push('\n');
+        }
+    }
+    text
+}
+
+fn is_fallback(node: &Node) -> bool {
+    node.is_element() && node.tag_name().name() == "Fallback" && node.tag_name().namespace() == Some(MARKUP_COMPATIBILITY_NAMESPACE)
+}
+
+/// IDs of the styles that start a section: the built-in title and level 1-2 heading styles, and
+/// custom styles at outline level 1 or 2.
+fn section_heading_styles(styles: &str) -> Result<HashSet<String>> {
+    let xml = XmlDocument::parse(styles)?;
+    let ids = xml.descendants()
+        .filter(|node| is_word_element(node, "style"))
+        .filter(|style| {
+            let name = word_child_value(*style, "name").map(str::to_lowercase);
+            let outline_level = style.children()
+                .find(|node| is_word_element(node, "pPr"))
+                .and_then(|properties| word_child_value(properties, "outlineLvl"));
+            name.is_some_and(|name| SECTION_STYLE_NAMES.contains(&name.as_str()))
+                || matches!(outline_level, Some("0" | "1"))
+        })
+        .filter_map(|style| style.attribute((WORD_NAMESPACE, "styleId")))
+        .map(str::to_string)
+        .collect();
+    Ok(ids)
+}
+
+/// The `w:val` of the first child element called `name`.
+fn word_child_value<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| is_word_element(child, name))
+        .and_then(|child| child.attribute((WORD_NAMESPACE, "val")))
+}
+
+fn is_section_heading(paragraph: Node, heading_styles: &HashSet<String>) -> bool {
+    let Some(properties) = paragraph.children().find(|node| is_word_element(node, "pPr")) else {
+        return false;
+    };
+    let style = word_child_value(properties, "pStyle");
+    style.is_some_and(|style| heading_styles.contains(style))
+        || matches!(word_child_value(properties, "outlineLvl"), Some("0" | "1"))
+}
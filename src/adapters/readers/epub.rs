@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::path::Path;
+use anyhow::{Context, Result};
+use log::debug;
+use roxmltree::Document as XmlDocument;
+use zip::ZipArchive;
+use crate::adapters::input::{Section, TextFormat};
+use super::{read_zip_entry, DocumentReader};
+use super::html::html_to_text;
+
+/// Reads EPUB books in spine order, producing one section per chapter file.
+pub struct EpubReader;
+
+impl DocumentReader for EpubReader {
+    fn extensions(&self) -> &[&str] {
+        &["epub"]
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<Section>> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open file: {:?}", path))?;
+        let mut archive = ZipArchive::new(file)
+            .with_context(|| format!("Failed to open EPUB archive: {:?}", path))?;
+
+        let container = read_zip_entry(&mut archive, "META-INF/container.xml")?;
+        let package_path = package_path(&container)?;
+        let package = read_zip_entry(&mut archive, &package_path)?;
+        let base_dir = package_path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+
+        let mut sections = Vec::new();
+        for href in spine_hrefs(&package)? {
+            let chapter_path = resolve_href(base_dir, &href);
+            let chapter = read_zip_entry(&mut archive, &chapter_path)?;
+            let text = html_to_text(&chapter);
+            if text.is_empty() {
+                continue;
+            }
+            let title = text
+                .lines()
+                .find_map(|line| line.strip_prefix('#').map(|heading| heading.trim_start_matches('#').trim().to_string()));
+            let section = Section::new(text);
+            sections.push(match title {
+                Some(title) => section.with_title(title),
+                None => section,
+            });
+        }
+        debug!("Read {} chapters from EPUB: {:?}", sections.len(), path);
+        Ok(sections)
+    }
+
+    fn text_format(&self, _path: &Path) -> TextFormat {
+        TextFormat::Markdown
+    }
+}
+
+fn package_path(container: &str) -> Result<String> {
+    let xml = XmlDocument::parse(container).context("Failed to parse EPUB container.xml")?;
+    xml.descendants()
+        .find(|node| node.has_tag_name("rootfile"))
+        .and_then(|node| node.attribute("full-path"))
+        .map(|path| path.to_string())
+        .context("EPUB container.xml has no rootfile")
+}
+
+fn spine_hrefs(package: &str) -> Result<Vec<String>> {
+    let xml = XmlDocument::parse(package).context("Failed to parse EPUB package document")?;
+    let manifest: Vec<(&str, &str)> = xml.descendants()
+        .filter(|node| node.has_tag_name("item"))
+        .filter_map(|node| Some((node.attribute("id")?, node.attribute("href")?)))
+        .collect();
+
+    let hrefs = xml.descendants()
+        .filter(|node| node.has_tag_name("itemref"))
+        .filter(|node| node.attribute("linear") != Some("no"))
+        .filter_map(|node| node.attribute("idref"))
+        .filter_map(|idref| manifest.iter().find(|(id, _)| *id == idref))
+        .map(|(_, href)| href.to_string())
+        .collect();
+    Ok(hrefs)
+}
+
+fn resolve_href(base_dir: &str, href: &str) -> String {
+    let href = percent_decode(href.split('#').next().unwrap_or(href));
+    let mut parts: Vec<&str> = base_dir.split('/').filter(|part| !part.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {},
+            ".." => { parts.pop(); },
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Decodes the `%XX` escapes of an href into the archive entry name; malformed escapes are kept as is.
+fn percent_decode(href: &str) -> String {
+    let bytes = href.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i + 1..i + 3) {
+            Some(hex) if bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit) => {
+                std::str::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            },
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
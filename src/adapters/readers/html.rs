@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use scraper::{ElementRef, Html, Node, Selector};
+use crate::adapters::input::{Section, TextFormat};
+use super::DocumentReader;
+
+const BOILERPLATE_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "nav", "aside", "form", "iframe", "svg", "template", "button",
+];
+
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "div", "section", "article", "main", "header", "footer", "blockquote", "ul", "ol",
+    "table", "tr", "dl", "dt", "dd", "figure", "figcaption", "hr",
+];
+
+pub struct HtmlReader;
+
+impl DocumentReader for HtmlReader {
+    fn extensions(&self) -> &[&str] {
+        &["html", "htm"]
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<Section>> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("Failed to read file: {:?}", path))?;
+        let text = html_to_text(&String::from_utf8_lossy(&bytes));
+        if text.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(vec![Section::new(text)])
+    }
+
+    fn text_format(&self, _path: &Path) -> TextFormat {
+        TextFormat::Markdown
+    }
+}
+
+/// Converts an HTML page to plain text, dropping navigation, scripts and styles.
+/// Headings are kept as Markdown headings and `<pre>` blocks as fenced code blocks.
+pub fn html_to_text(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let content_selector = Selector::parse("main, article").expect("Invalid content selector");
+    let body_selector = Selector::parse("body").expect("Invalid body selector");
+
+    // A page may hold several articles, e.g. a blog index; nested matches render with their parent
+    let contents: Vec<ElementRef> = document.select(&content_selector)
+        .filter(|content| !content.ancestors().filter_map(ElementRef::wrap).any(is_content_element))
+        .collect();
+
+    let mut output = String::new();
+    if contents.is_empty() {
+        match document.select(&body_selector).next() {
+            Some(body) => render_element(body, false, &mut output),
+            None => render_element(document.root_element(), false, &mut output),
+        }
+    }
+    for content in contents {
+        render_element(content, true, &mut output);
+    }
+    normalize_blank_lines(&output)
+}
+
+fn is_content_element(element: ElementRef) -> bool {
+    matches!(element.value().name(), "main" | "article")
+}
+
+fn render_element(element: ElementRef, in_content: bool, output: &mut String) {
+    let name = element.value().name();
+    if BOILERPLATE_ELEMENTS.contains(&name) {
+        return;
+    }
+    // Page-level headers and footers are site chrome; inside an article they hold the title and byline.
+    if (name == "header" || name == "footer") && !in_content {
+        return;
+    }
+    let in_content = in_content || is_content_element(element);
+
+    match name {
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = name[1..].parse::<usize>().unwrap_or(1);
+            let heading = collapse_whitespace(&element.text().collect::<String>());
+            if !heading.is_empty() {
+                output.push_str(&format!("\n\n{} {}\n\n", "#".repeat(level), heading));
+            }
+        },
+        "pre" => {
+            let code = element.text().collect::<String>();
+            output.push_str(&format!("\n\n```\n{}\n```\n\n", code.trim_end()));
+        },
+        "code" => {
+            let code = element.text().collect::<String>();
+            push_inline(output, &format!("`{}`", code.trim()));
+        },
+        "br" => output.push('\n'),
+        "li" => {
+            output.push_str("\n- ");
+            render_children(element, in_content, output);
+        },
+        _ if BLOCK_ELEMENTS.contains(&name) => {
+            output.push_str("\n\n");
+            render_children(element, in_content, output);
+            output.push_str("\n\n");
+        },
+        _ => render_children(element, in_content, output),
+    }
+}
+
+fn render_children(element: ElementRef, in_content: bool, output: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => {
+                push_inline(output, &collapse_whitespace(text));
+            },
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    render_element(child, in_content, output);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+fn push_inline(output: &mut String, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let starts_with_punctuation = text.starts_with(|c: char| ".,;:!?)]".contains(c));
+    if !output.is_empty() && !output.ends_with(char::is_whitespace) && !starts_with_punctuation {
+        output.push(' ');
+    }
+    output.push_str(text);
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn normalize_blank_lines(text: &str) -> String {
+    let mut result = String::new();
+    let mut in_code_block = false;
+    let mut blank_run = 0;
+    for line in text.lines() {
+        if line.starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        let line = if in_code_block { line } else { line.trim() };
+        if line.is_empty() && !in_code_block {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    result.trim().to_string()
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use crate::adapters::input::{Section, TextFormat};
+
+#[cfg(feature = "pdf")]
+pub mod pdf;
+#[cfg(feature = "html")]
+pub mod html;
+#[cfg(feature = "html")]
+pub mod warc;
+#[cfg(feature = "epub")]
+pub mod epub;
+#[cfg(feature = "docx")]
+pub mod docx;
+
+pub trait DocumentReader {
+    fn extensions(&self) -> &[&str];
+
+    fn read(&self, path: &Path) -> Result<Vec<Section>>;
+
+    fn text_format(&self, _path: &Path) -> TextFormat {
+        TextFormat::Plain
+    }
+}
+
+pub struct PlainTextReader;
+
+impl DocumentReader for PlainTextReader {
+    fn extensions(&self) -> &[&str] {
+        &["txt", "md"]
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<Section>> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {:?}", path))?;
+        Ok(vec![Section::new(content)])
+    }
+
+    fn text_format(&self, path: &Path) -> TextFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown") => {
+                TextFormat::Markdown
+            },
+            _ => TextFormat::Plain,
+        }
+    }
+}
+
+pub fn default_readers() -> Vec<Box<dyn DocumentReader + Send + Sync>> {
+    vec![
+        Box::new(PlainTextReader),
+        #[cfg(feature = "pdf")]
+        Box::new(pdf::PdfReader),
+        #[cfg(feature = "html")]
+        Box::new(html::HtmlReader),
+        #[cfg(feature = "html")]
+        Box::new(warc::WarcReader),
+        #[cfg(feature = "epub")]
+        Box::new(epub::EpubReader),
+        #[cfg(feature = "docx")]
+        Box::new(docx::DocxReader),
+    ]
+}
+
+#[cfg(any(feature = "epub", feature = "docx"))]
+fn read_zip_entry<R: std::io::Read + std::io::Seek>(archive: &mut zip::ZipArchive<R>, name: &str) -> Result<String> {
+    use std::io::Read;
+
+    let mut entry = archive.by_name(name)
+        .with_context(|| format!("Missing archive entry: {}", name))?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content)
+        .with_context(|| format!("Failed to read archive entry: {}", name))?;
+    Ok(content)
+}
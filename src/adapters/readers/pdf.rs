@@ -0,0 +1,30 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use crate::adapters::input::Section;
+use super::DocumentReader;
+
+pub struct PdfReader;
+
+impl DocumentReader for PdfReader {
+    fn extensions(&self) -> &[&str] {
+        &["pdf"]
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<Section>> {
+        // pdf-extract panics on some malformed PDFs instead of returning an error
+        let pages = panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_by_pages(path)))
+            .map_err(|_| anyhow!("PDF parser panicked"))
+            .and_then(|pages| pages.map_err(Into::into))
+            .with_context(|| format!("Failed to extract text from PDF: {:?}", path))?;
+        debug!("Extracted {} pages from PDF: {:?}", pages.len(), path);
+        let sections = pages
+            .into_iter()
+            .enumerate()
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(index, text)| Section::new(text).with_page(index as u32 + 1))
+            .collect();
+        Ok(sections)
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use crate::adapters::input::{Section, TextFormat};
+use super::DocumentReader;
+use super::html::html_to_text;
+
+const HEADER_TERMINATOR: &[u8] = b"\r\n\r\n";
+
+/// Reads uncompressed WARC archives, turning every archived HTML page into its own section.
+pub struct WarcReader;
+
+struct WarcRecord<'a> {
+    headers: Vec<(String, String)>,
+    block: &'a [u8],
+}
+
+impl WarcRecord<'_> {
+    fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+impl DocumentReader for WarcReader {
+    fn extensions(&self) -> &[&str] {
+        &["warc"]
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<Section>> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("Failed to read file: {:?}", path))?;
+        let mut sections = Vec::new();
+        let mut rest = bytes.as_slice();
+        while let Some(record) = next_record(&mut rest)
+            .with_context(|| format!("Failed to parse WARC archive: {:?}", path))?
+        {
+            let html = match record.header("WARC-Type") {
+                Some("response") => html_from_http_response(record.block),
+                Some("resource") if is_html(record.header("Content-Type")) => {
+                    Some(String::from_utf8_lossy(record.block).into_owned())
+                },
+                _ => None,
+            };
+            let Some(html) = html else { continue };
+            let text = html_to_text(&html);
+            if text.is_empty() {
+                continue;
+            }
+            // The target URI tells the metadata sidecar which archived page a chunk came from
+            let section = match record.header("WARC-Target-URI") {
+                Some(uri) => {
+                    debug!("Extracted {} from WARC archive", uri);
+                    Section::new(text).with_title(uri.to_string())
+                },
+                None => Section::new(text),
+            };
+            sections.push(section);
+        }
+        Ok(sections)
+    }
+
+    fn text_format(&self, _path: &Path) -> TextFormat {
+        TextFormat::Markdown
+    }
+}
+
+fn next_record<'a>(rest: &mut &'a [u8]) -> Result<Option<WarcRecord<'a>>> {
+    while let Some(stripped) = rest.strip_prefix(b"\r\n").or_else(|| rest.strip_prefix(b"\n")) {
+        *rest = stripped;
+    }
+    if rest.is_empty() {
+        return Ok(None);
+    }
+    if !rest.starts_with(b"WARC/") {
+        anyhow::bail!("Expected a WARC record header");
+    }
+
+    let header_end = find(rest, HEADER_TERMINATOR).context("Unterminated WARC record header")?;
+    let headers = parse_headers(&rest[..header_end]);
+    let content_length: usize = find_header(&headers, "Content-Length")
+        .context("WARC record is missing Content-Length")?
+        .parse()
+        .context("Invalid WARC Content-Length")?;
+
+    let block_start = header_end + HEADER_TERMINATOR.len();
+    let block_end = block_start.checked_add(content_length)
+        .filter(|&block_end| block_end <= rest.len())
+        .context("Truncated WARC record: Content-Length runs past the end of the archive")?;
+    let block = &rest[block_start..block_end];
+    *rest = &rest[block_end..];
+    Ok(Some(WarcRecord { headers, block }))
+}
+
+fn html_from_http_response(block: &[u8]) -> Option<String> {
+    let header_end = find(block, HEADER_TERMINATOR)?;
+    let headers = parse_headers(&block[..header_end]);
+    if !is_html(find_header(&headers, "Content-Type")) {
+        return None;
+    }
+    if find_header(&headers, "Content-Encoding").is_some_and(|encoding| !encoding.eq_ignore_ascii_case("identity")) {
+        warn!("Skipping compressed HTTP response in WARC archive");
+        return None;
+    }
+
+    let body = &block[header_end + HEADER_TERMINATOR.len()..];
+    let chunked = find_header(&headers, "Transfer-Encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+    let body = if chunked { decode_chunked(body)? } else { body.to_vec() };
+    Some(String::from_utf8_lossy(&body).into_owned())
+}
+
+fn decode_chunked(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = find(body, b"\r\n")?;
+        let size_line = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = usize::from_str_radix(size_line.split(';').next()?.trim(), 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+fn parse_headers(raw: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(raw)
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn is_html(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|content_type| content_type.to_ascii_lowercase().contains("text/html"))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
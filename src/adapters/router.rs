@@ -0,0 +1,304 @@
+use super::llm::{LLMInterface, LLMProvider, OutputFormat};
+use crate::core::fine_tuning::FineTuneConfig;
+use super::openai::OpenAI;
+use super::anthropic::Anthropic;
+use super::azure::AzureOpenAI;
+use super::gemini::Gemini;
+use super::error::{find_llm_error, LLMError};
+use super::http::DEFAULT_MAX_RETRIES;
+use crate::utils::lib::retry;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use crate::utils::llm::FromLLMResponse;
+use std::sync::Mutex;
+use std::path::PathBuf;
+use tokio::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+/// How long a provider is left out of the rotation after a rate limit or outage without a Retry-After.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+/// Transient errors in a row before a provider is treated as down; a single one is only failed over.
+const TRANSIENT_FAILURES_BEFORE_COOLDOWN: u32 = 3;
+
+/// One of the built-in providers, so providers of different types can share a `Router`.
+pub enum AnyProvider {
+    OpenAI(OpenAI),
+    Anthropic(Anthropic),
+    Azure(AzureOpenAI),
+    Gemini(Gemini),
+}
+
+macro_rules! delegate {
+    ($self:ident, $provider:ident => $call:expr) => {
+        match $self {
+            AnyProvider::OpenAI($provider) => $call,
+            AnyProvider::Anthropic($provider) => $call,
+            AnyProvider::Azure($provider) => $call,
+            AnyProvider::Gemini($provider) => $call,
+        }
+    };
+}
+
+impl LLMProvider for AnyProvider {
+    fn generate_headers(&self) -> Result<HeaderMap> {
+        delegate!(self, provider => provider.generate_headers())
+    }
+
+    fn generate_request_body(&self, sys_prompt: &str, user_prompt: &str, output_format: &OutputFormat) -> Result<Value> {
+        delegate!(self, provider => provider.generate_request_body(sys_prompt, user_prompt, output_format))
+    }
+}
+
+impl LLMInterface for AnyProvider {
+    async fn send_request<T: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<T> {
+        delegate!(self, provider => provider.send_request(sys_prompt, user_prompt).await)
+    }
+
+    async fn upload_file(&self, file_path: PathBuf) -> Result<Value> {
+        delegate!(self, provider => provider.upload_file(file_path).await)
+    }
+
+    async fn create_fine_tuning_job(&self, training_file: &str, validation_file: Option<&str>, config: &FineTuneConfig) -> Result<Value> {
+        delegate!(self, provider => provider.create_fine_tuning_job(training_file, validation_file, config).await)
+    }
+
+    async fn train(&self, file_path: PathBuf, validation_file_path: Option<PathBuf>, config: &FineTuneConfig) -> Result<Value> {
+        delegate!(self, provider => provider.train(file_path, validation_file_path, config).await)
+    }
+
+    async fn get_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        delegate!(self, provider => provider.get_fine_tuning_job(job_id).await)
+    }
+
+    async fn list_fine_tuning_events(&self, job_id: &str) -> Result<Value> {
+        delegate!(self, provider => provider.list_fine_tuning_events(job_id).await)
+    }
+
+    async fn list_fine_tuning_jobs(&self, limit: u32) -> Result<Value> {
+        delegate!(self, provider => provider.list_fine_tuning_jobs(limit).await)
+    }
+
+    async fn cancel_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        delegate!(self, provider => provider.cancel_fine_tuning_job(job_id).await)
+    }
+
+    async fn list_fine_tuning_checkpoints(&self, job_id: &str) -> Result<Value> {
+        delegate!(self, provider => provider.list_fine_tuning_checkpoints(job_id).await)
+    }
+
+    fn supports_fine_tuning(&self) -> bool {
+        delegate!(self, provider => provider.supports_fine_tuning())
+    }
+
+    fn name(&self) -> String {
+        delegate!(self, provider => provider.name())
+    }
+
+    fn temperature(&self) -> Option<f32> {
+        delegate!(self, provider => provider.temperature())
+    }
+}
+
+struct RouteState {
+    current_weight: i64,
+    cooldown_until: Option<Instant>,
+    transient_failures: u32,
+}
+
+/// Spreads requests over several providers by smooth weighted round-robin. When a provider fails
+/// with a rate limit or an outage, the request fails over to the next provider. A rate-limited
+/// provider sits out until its Retry-After (or a short cooldown) has passed; a provider with an
+/// outage only sits out after several transient errors in a row.
+///
+/// The router owns retries: give routed providers `with_retries(0)` so a rate limit reaches it at
+/// once instead of after the provider's own retries. When every provider has failed or is cooling
+/// down, the request is retried up to `max_retries` times.
+pub struct Router<T: LLMInterface> {
+    providers: Vec<(T, u32)>,
+    state: Mutex<Vec<RouteState>>,
+    max_retries: u32,
+    delay: Duration,
+}
+
+impl<T: LLMInterface> Router<T> {
+    pub fn new() -> Self {
+        Self { providers: Vec::new(), state: Mutex::new(Vec::new()), max_retries: DEFAULT_MAX_RETRIES, delay: Duration::from_secs(1) }
+    }
+
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Adds a provider that receives `weight` out of every `total weight` requests.
+    pub fn with_provider(mut self, provider: T, weight: u32) -> Self {
+        self.providers.push((provider, weight.max(1)));
+        self.state.get_mut().expect("Router state lock poisoned")
+            .push(RouteState { current_weight: 0, cooldown_until: None, transient_failures: 0 });
+        self
+    }
+
+    /// Order in which providers are tried for the next request: the weighted round-robin pick first,
+    /// then the other available providers in configured order. Providers cooling down are left out;
+    /// when all of them are, the error says how long until the first one is back.
+    fn route(&self) -> Result<Vec<usize>> {
+        if self.providers.is_empty() {
+            return Err(LLMError::InvalidRequest("Router has no providers".to_string()).into());
+        }
+        let mut state = self.state.lock().expect("Router state lock poisoned");
+        let now = Instant::now();
+        let available: Vec<usize> = (0..self.providers.len())
+            .filter(|&i| state[i].cooldown_until.is_none_or(|until| until <= now))
+            .collect();
+        if available.is_empty() {
+            let retry_after = state.iter()
+                .filter_map(|route| route.cooldown_until)
+                .min()
+                .map(|until| until.saturating_duration_since(now));
+            return Err(LLMError::RateLimited {
+                message: "All routed providers are cooling down".to_string(),
+                retry_after,
+            }.into());
+        }
+
+        let total: i64 = available.iter().map(|&i| self.providers[i].1 as i64).sum();
+        for &i in &available {
+            state[i].current_weight += self.providers[i].1 as i64;
+        }
+        let picked = *available.iter()
+            .max_by_key(|&&i| (state[i].current_weight, std::cmp::Reverse(i)))
+            .expect("Router has an available provider");
+        state[picked].current_weight -= total;
+
+        let mut order = vec![picked];
+        order.extend((1..self.providers.len())
+            .map(|offset| (picked + offset) % self.providers.len())
+            .filter(|i| available.contains(i)));
+        Ok(order)
+    }
+
+    /// Tries each provider in the route once, failing over on rate limits and outages.
+    async fn send_once<R: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<(R, String)> {
+        let mut last_error = None;
+        for index in self.route()? {
+            let provider = &self.providers[index].0;
+            match provider.send_request_attributed(sys_prompt, user_prompt).await {
+                Ok(response) => {
+                    self.record_success(index);
+                    return Ok(response);
+                },
+                Err(e) if should_fail_over(&e) => {
+                    warn!("Provider {} failed, failing over: {:#}", provider.name(), e);
+                    self.record_failure(index, &e);
+                    last_error = Some(e);
+                },
+                Err(e) => return Err(e).with_context(|| format!("Request to {} failed", provider.name())),
+            }
+        }
+        Err(last_error.context("Router has no providers")?)
+            .context("All routed providers failed")
+    }
+
+    fn record_success(&self, index: usize) {
+        let mut state = self.state.lock().expect("Router state lock poisoned");
+        state[index].transient_failures = 0;
+    }
+
+    /// Rate limits take the provider out of the rotation at once. Transient errors are what retries
+    /// are for, so they only do once they keep happening.
+    fn record_failure(&self, index: usize, error: &anyhow::Error) {
+        let mut state = self.state.lock().expect("Router state lock poisoned");
+        let route = &mut state[index];
+        let retry_after = match find_llm_error(error) {
+            Some(LLMError::RateLimited { retry_after, .. }) => *retry_after,
+            _ => {
+                route.transient_failures += 1;
+                if route.transient_failures < TRANSIENT_FAILURES_BEFORE_COOLDOWN {
+                    return;
+                }
+                None
+            },
+        };
+        route.transient_failures = 0;
+        route.cooldown_until = Some(Instant::now() + retry_after.unwrap_or(DEFAULT_COOLDOWN));
+    }
+
+    /// The first provider able to fine-tune; uploads and jobs must go to the same one.
+    fn fine_tuning_provider(&self) -> Result<&T> {
+        self.providers.iter()
+            .map(|(provider, _)| provider)
+            .find(|provider| provider.supports_fine_tuning())
+            .ok_or_else(|| LLMError::InvalidRequest("No routed provider supports fine-tuning".to_string()).into())
+    }
+}
+
+impl<T: LLMInterface> Default for Router<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rate limits and outages are worth another provider; other errors would fail there too.
+fn should_fail_over(error: &anyhow::Error) -> bool {
+    matches!(find_llm_error(error), Some(LLMError::RateLimited { .. }) | Some(LLMError::Transient(_)))
+}
+
+impl<T: LLMInterface> LLMInterface for Router<T> {
+    async fn send_request<R: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<R> {
+        self.send_request_attributed(sys_prompt, user_prompt).await
+            .map(|(response, _)| response)
+    }
+
+    async fn send_request_attributed<R: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> Result<(R, String)> {
+        retry(self.max_retries, self.delay, || self.send_once(sys_prompt, user_prompt)).await
+    }
+
+    async fn upload_file(&self, file_path: PathBuf) -> Result<Value> {
+        self.fine_tuning_provider()?.upload_file(file_path).await
+    }
+
+    async fn create_fine_tuning_job(&self, training_file: &str, validation_file: Option<&str>, config: &FineTuneConfig) -> Result<Value> {
+        self.fine_tuning_provider()?.create_fine_tuning_job(training_file, validation_file, config).await
+    }
+
+    async fn train(&self, file_path: PathBuf, validation_file_path: Option<PathBuf>, config: &FineTuneConfig) -> Result<Value> {
+        let provider = self.fine_tuning_provider()?;
+        info!("Fine-tuning with {}", provider.name());
+        provider.train(file_path, validation_file_path, config).await
+    }
+
+    async fn get_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        self.fine_tuning_provider()?.get_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_events(&self, job_id: &str) -> Result<Value> {
+        self.fine_tuning_provider()?.list_fine_tuning_events(job_id).await
+    }
+
+    async fn list_fine_tuning_jobs(&self, limit: u32) -> Result<Value> {
+        self.fine_tuning_provider()?.list_fine_tuning_jobs(limit).await
+    }
+
+    async fn cancel_fine_tuning_job(&self, job_id: &str) -> Result<Value> {
+        self.fine_tuning_provider()?.cancel_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_checkpoints(&self, job_id: &str) -> Result<Value> {
+        self.fine_tuning_provider()?.list_fine_tuning_checkpoints(job_id).await
+    }
+
+    fn supports_fine_tuning(&self) -> bool {
+        self.providers.iter().any(|(provider, _)| provider.supports_fine_tuning())
+    }
+
+    fn name(&self) -> String {
+        let names: Vec<String> = self.providers.iter().map(|(provider, _)| provider.name()).collect();
+        format!("router({})", names.join(", "))
+    }
+}
@@ -1,61 +1,266 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use crate::adapters::input::*;
 use tokio::sync::Mutex;
 use std::{
-    fs::File,
+    fs::{File, OpenOptions},
     io:: BufWriter,
     sync::Arc,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 use tokio;
 use crate::utils::lib::*;
+use crate::utils::rate_limit::RateLimiter;
 use clap::Parser;
 use crate::adapters::openai::*;
+use crate::adapters::anthropic::*;
+use crate::adapters::azure::*;
+use crate::adapters::gemini::*;
+use crate::adapters::router::*;
+use crate::adapters::cache::*;
+use crate::adapters::http::DEFAULT_MAX_RETRIES;
+use crate::cli::jobs::run_jobs_command;
+use crate::cli::train::run_train_command;
+use crate::adapters::llm::{LLMInterface, Provider};
 use crate::core::assimilator::*;
+use crate::core::manifest::*;
+use crate::core::report::HarvestReport;
+use crate::core::fine_tuning::FineTuneMethod;
 use log::debug;
+use tokio::time::Duration;
 
 pub async fn run_cli_interface() -> Result<()> {
     let args = Args::parse();
-    let output_path = get_output_file_path(args.output);
-    let writer = create_writer(&output_path)
+    match &args.command {
+        Some(Command::Jobs { action }) => return run_jobs_command(&args, action).await,
+        Some(Command::Train { file }) => return run_train_command(&args, file.clone()).await,
+        Some(Command::Run) | Some(Command::Generate) | None => {},
+    }
+    debug!("Creating FileInputAdapter with input path: {:?} and chunk size: {}", args.input, args.chunk_size);
+    let input_adapter = FileInputAdapter::new(args.input.clone(), args.chunk_size)
+        .with_recursive(args.recursive)
+        .with_include(args.include.clone())
+        .with_exclude(args.exclude.clone())
+        .with_follow_symlinks(args.follow_symlinks)
+        .with_max_file_size(args.max_file_size)
+        .with_chunk_unit(args.chunk_unit)
+        .with_chunk_overlap(args.chunk_overlap)
+        .with_splitter(args.splitter)
+        .with_tokenizer_model(args.model());
+    run_with_input(args, input_adapter).await
+}
+
+pub async fn run_with_input<I: InputAdapter + Send + 'static>(args: Args, input_adapter: I) -> Result<()> {
+    if args.routes.is_empty() {
+        let llm = build_provider(&args, args.provider, args.model(), DEFAULT_MAX_RETRIES)?;
+        return run_with_cache(args, input_adapter, llm).await;
+    }
+
+    // The router retries, so a rate-limited provider fails over at once instead of retrying first
+    let mut router = Router::new();
+    for route in &args.routes {
+        let model = route.model.clone().unwrap_or_else(|| route.provider.default_model().to_string());
+        debug!("Routing {} of requests to {:?} with model {}", route.weight, route.provider, model);
+        router = router.with_provider(build_provider(&args, route.provider, model, 0)?, route.weight);
+    }
+    run_with_cache(args, input_adapter, router).await
+}
+
+async fn run_with_cache<I: InputAdapter + Send + 'static, T: LLMInterface>(args: Args, input_adapter: I, llm: T) -> Result<()> {
+    match args.cache_dir.clone() {
+        Some(cache_dir) => {
+            debug!("Caching responses in {:?} with mode {:?}", cache_dir, args.cache_mode);
+            let llm = CachedLLM::new(llm, cache_dir).with_mode(args.cache_mode);
+            run_with_llm(args, input_adapter, llm).await
+        },
+        None => run_with_llm(args, input_adapter, llm).await,
+    }
+}
+
+/// Connection options such as --base-url configure providers of the --provider type; other providers
+/// use their defaults. Each provider gets its own rate limiter, as quotas are per provider.
+pub(crate) fn build_provider(args: &Args, provider: Provider, model: String, max_retries: u32) -> Result<AnyProvider> {
+    let configured = provider == args.provider;
+    let api_key_env = args.api_key_env.clone()
+        .filter(|_| configured)
+        .unwrap_or_else(|| provider.default_api_key_env().to_string());
+    let base_url = args.base_url.clone().filter(|_| configured);
+    let rate_limiter = Arc::new(RateLimiter::new(args.requests_per_minute, args.tokens_per_minute));
+    let max_tokens = provider.default_max_tokens();
+
+    let llm = match provider {
+        Provider::OpenAI => {
+            let mut llm = OpenAI::new(model, 1.0, max_tokens)
+                .with_rate_limiter(rate_limiter)
+                .with_retries(max_retries)
+                .with_api_key_env(api_key_env);
+            if configured {
+                llm = llm.with_extra_headers(args.extra_headers());
+            }
+            if let Some(base_url) = base_url {
+                llm = llm.with_base_url(base_url);
+            }
+            AnyProvider::OpenAI(llm)
+        },
+        Provider::Anthropic => AnyProvider::Anthropic(Anthropic::new(model, 1.0, max_tokens)
+            .with_rate_limiter(rate_limiter)
+            .with_retries(max_retries)
+            .with_api_key_env(api_key_env)),
+        Provider::Azure => {
+            let endpoint = base_url
+                .context("--base-url must be set to the Azure OpenAI resource endpoint")?;
+            let deployment = args.deployment.clone()
+                .filter(|_| configured)
+                .unwrap_or_else(|| model.clone());
+            AnyProvider::Azure(AzureOpenAI::new(endpoint, deployment, model, 1.0, max_tokens)
+                .with_rate_limiter(rate_limiter)
+                .with_retries(max_retries)
+                .with_api_version(args.api_version.clone())
+                .with_api_key_env(api_key_env))
+        },
+        Provider::Gemini => AnyProvider::Gemini(Gemini::new(model, 1.0, max_tokens)
+            .with_rate_limiter(rate_limiter)
+            .with_retries(max_retries)
+            .with_api_key_env(api_key_env)),
+    };
+    Ok(llm)
+}
+
+pub async fn run_with_llm<I: InputAdapter + Send + 'static, T: LLMInterface>(args: Args, input_adapter: I, llm: T) -> Result<()> {
+    // Checked before harvesting so a bad config does not surface only after the whole run
+    let fine_tune_config = args.fine_tune_config()?;
+    let should_train = args.should_train();
+    if fine_tune_config.method == FineTuneMethod::Dpo {
+        bail!("--method dpo needs a preference dataset, which harvesting does not produce. Build one and fine-tune on it with: neuralassimilator train <file> --method dpo");
+    }
+    let output_path = if args.resume {
+        get_resume_file_path(args.output).context("Failed to find run to resume")?
+    } else {
+        get_output_file_path(args.output)
+    };
+    let writer = create_writer(&output_path, args.resume)
         .context("Failed to create writer")?;
-    let llm = OpenAI::new(args.model, 1.0, 16000);
-    let assimilator = Assimilator::new(llm, writer);
-    
-    let prompts = assimilator.tune_prompt(&args.use_case).await
-        .context("Failed to tune prompt")?;
+    let manifest_path = get_manifest_file_path(&output_path);
+    let manifest = if args.resume {
+        println!("Resuming run {:?}", output_path);
+        RunManifest::open(manifest_path).context("Failed to open run manifest")?
+    } else {
+        RunManifest::create(manifest_path).context("Failed to create run manifest")?
+    };
+
+    let supports_fine_tuning = llm.supports_fine_tuning();
+    let llm_name = llm.name();
+    let mut assimilator = Assimilator::new(llm, writer)
+        .with_rejects_path(get_rejects_file_path(&output_path))
+        .with_manifest(manifest)
+        .with_concurrency(args.concurrency)
+        .with_fine_tune_config(fine_tune_config);
+    if let Some(max_failure_rate) = args.max_failure_rate {
+        assimilator = assimilator.with_max_failure_rate(max_failure_rate);
+    }
+    if args.metadata {
+        let metadata_path = get_metadata_file_path(&output_path);
+        if args.resume {
+            check_metadata_covers_output(&output_path, &metadata_path)?;
+        }
+        let metadata_writer = create_writer(&metadata_path, args.resume)
+            .context("Failed to create metadata writer")?;
+        assimilator = assimilator.with_metadata_writer(metadata_writer);
+    }
     
-    let chunk_prompt_pairs = prepare_chunk_prompt_pairs(args.input, args.chunk_size, &prompts)
-        .context("Failed to prepare chunk-prompt pairs")?;
+    // Tuned prompts differ between calls, so a resumed run reuses the prompts its manifest was keyed on
+    let prompts_path = get_prompts_file_path(&output_path);
+    let prompts = if args.resume {
+        load_prompts(&prompts_path).context("Failed to load prompts of resumed run")?
+    } else {
+        let prompts = assimilator.tune_prompt(&args.use_case).await
+            .context("Failed to tune prompt")?;
+        save_prompts(&prompts_path, &prompts).context("Failed to save prompts")?;
+        prompts
+    };
     
-    assimilator.harvest(chunk_prompt_pairs).await
+    let report = assimilator.assimilate(input_adapter, &prompts).await
         .context("Failed to harvest chunk-prompt pairs")?;
+    println!(
+        "Harvested {} instructions, {} pairs failed, {} already done",
+        report.succeeded, report.failed, report.skipped
+    );
+    if report.failed > 0 {
+        let causes: Vec<String> = report.failure_causes.iter()
+            .map(|(cause, count)| format!("{} {}", count, cause))
+            .collect();
+        println!("Failed pairs ({}) written to {:?}", causes.join(", "), get_rejects_file_path(&output_path));
+    }
+    if let Some(max_failure_rate) = args.max_failure_rate {
+        check_failure_rate(&report, max_failure_rate)?;
+    }
 
-    assimilator.train(output_path).await
+    if !should_train {
+        println!("Instructions are in {:?}. Fine-tune on them with: neuralassimilator train {:?}", output_path, output_path);
+        return Ok(());
+    }
+    if !supports_fine_tuning {
+        println!("Skipping fine-tuning: {} does not support it. Instructions are in {:?}", llm_name, output_path);
+        return Ok(());
+    }
+    let job = assimilator.train(output_path).await
         .context("Failed to fine-tune LLM")?;
+    if args.wait {
+        let job = assimilator.wait_for_training(&job, Duration::from_secs(args.poll_interval)).await
+            .context("Fine-tuning did not succeed")?;
+        println!("Fine-tuned model: {}", job["fine_tuned_model"].as_str().unwrap_or("unknown"));
+    }
     
     Ok(())
 }
 
-fn prepare_chunk_prompt_pairs(
-    input_path: PathBuf,
-    chunk_size: usize,
-    prompts: &[String]
-) -> Result<Vec<(String, String)>> {
-    debug!("Preparing chunk-prompt pairs");
-    debug!("Creating FileInputAdapter with input path: {:?} and chunk size: {}", input_path, chunk_size);
-    let input_adapter = FileInputAdapter::new(input_path, chunk_size);
-    let chunks = input_adapter.fetch_chunks()
-        .context("Failed to fetch chunks from input")?;
-    debug!("Creating chunk-prompt pairs");
-    let chunk_prompt_pairs = create_chunk_prompt_pairs(&chunks, prompts);
-    Ok(chunk_prompt_pairs)
+/// A run that stopped early is missing pairs and can be resumed; a run that completed above the
+/// threshold has every pair it will get, but is not trusted to fine-tune on.
+fn check_failure_rate(report: &HarvestReport, max_failure_rate: f64) -> Result<()> {
+    if report.aborted {
+        bail!(
+            "Harvest stopped early: {} of {} pairs failed ({:.1}%), above the maximum failure rate of {:.1}%. Fix the cause and continue with --resume",
+            report.failed, report.completed(), report.failure_rate() * 100.0, max_failure_rate * 100.0
+        );
+    }
+    if report.failure_rate() > max_failure_rate {
+        bail!(
+            "Harvest completed, but {} of {} pairs failed ({:.1}%), above the maximum failure rate of {:.1}%",
+            report.failed, report.completed(), report.failure_rate() * 100.0, max_failure_rate * 100.0
+        );
+    }
+    Ok(())
+}
+
+/// Line N of the metadata sidecar describes line N of the output, which only holds when a resumed
+/// run appends to a sidecar that covers every line written so far.
+fn check_metadata_covers_output(output_path: &Path, metadata_path: &Path) -> Result<()> {
+    if !metadata_path.exists() {
+        bail!(
+            "--metadata cannot be added when resuming a run that was started without it: {:?} does not exist",
+            metadata_path
+        );
+    }
+    let output_lines = count_lines(output_path).context("Failed to count output lines")?;
+    let metadata_lines = count_lines(metadata_path).context("Failed to count metadata lines")?;
+    if output_lines != metadata_lines {
+        bail!(
+            "--metadata cannot be used to resume this run: {:?} has {} lines but {:?} has {}",
+            metadata_path, metadata_lines, output_path, output_lines
+        );
+    }
+    Ok(())
 }
 
-fn create_writer(output_path: &PathBuf) -> Result<Arc<Mutex<BufWriter<File>>>> {
+fn create_writer(output_path: &PathBuf, append: bool) -> Result<Arc<Mutex<BufWriter<File>>>> {
     debug!("Creating writer for output path: {:?}", output_path);
-    let file = File::create(output_path)
-        .context("Failed to create output file")?;
+    let file = if append && output_path.exists() {
+        truncate_partial_line(output_path)?;
+        OpenOptions::new().append(true).open(output_path)
+            .context("Failed to open output file for appending")?
+    } else {
+        File::create(output_path)
+            .context("Failed to create output file")?
+    };
     let writer: Arc<Mutex<BufWriter<File>>> = Arc::new(Mutex::new(BufWriter::new(file)));
     Ok(writer)
-}
\ No newline at end of file
+}
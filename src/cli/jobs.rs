@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use chrono::DateTime;
+use crate::adapters::llm::LLMInterface;
+use crate::adapters::http::DEFAULT_MAX_RETRIES;
+use crate::cli::interface::build_provider;
+use crate::utils::lib::{Args, JobsCommand};
+
+pub async fn run_jobs_command(args: &Args, command: &JobsCommand) -> Result<()> {
+    let llm = build_provider(args, args.provider, args.model(), DEFAULT_MAX_RETRIES)?;
+    match command {
+        JobsCommand::List { limit } => {
+            let jobs = llm.list_fine_tuning_jobs(*limit).await
+                .context("Failed to list fine-tuning jobs")?;
+            let jobs = jobs["data"].as_array().cloned().unwrap_or_default();
+            if jobs.is_empty() {
+                println!("No fine-tuning jobs");
+            }
+            for job in &jobs {
+                println!(
+                    "{}  {:<20}  {:<10}  {:<30}  {}",
+                    field(job, "id"),
+                    format_timestamp(&job["created_at"]),
+                    field(job, "status"),
+                    field(job, "model"),
+                    field(job, "fine_tuned_model"),
+                );
+            }
+        },
+        JobsCommand::Show { id } => {
+            let job = llm.get_fine_tuning_job(id).await
+                .context("Failed to get fine-tuning job")?;
+            println!("{}", serde_json::to_string_pretty(&job).context("Failed to format fine-tuning job")?);
+        },
+        JobsCommand::Cancel { id } => {
+            let job = llm.cancel_fine_tuning_job(id).await
+                .context("Failed to cancel fine-tuning job")?;
+            println!("Cancelled fine-tuning job {}. Status: {}", id, field(&job, "status"));
+        },
+        JobsCommand::Checkpoints { id } => {
+            let checkpoints = llm.list_fine_tuning_checkpoints(id).await
+                .context("Failed to list fine-tuning checkpoints")?;
+            let checkpoints = checkpoints["data"].as_array().cloned().unwrap_or_default();
+            if checkpoints.is_empty() {
+                println!("No checkpoints for fine-tuning job {}", id);
+            }
+            for checkpoint in &checkpoints {
+                println!(
+                    "step {:<6}  train loss {:<8}  valid loss {:<8}  {}",
+                    checkpoint["step_number"],
+                    format_metric(&checkpoint["metrics"]["train_loss"]),
+                    format_metric(&checkpoint["metrics"]["valid_loss"]),
+                    field(checkpoint, "fine_tuned_model_checkpoint"),
+                );
+            }
+        },
+    }
+    Ok(())
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> &'a str {
+    value[name].as_str().unwrap_or("-")
+}
+
+fn format_timestamp(value: &Value) -> String {
+    value.as_i64()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_metric(value: &Value) -> String {
+    value.as_f64().map(|metric| format!("{:.4}", metric)).unwrap_or_else(|| "-".to_string())
+}
@@ -1 +1,3 @@
-pub mod interface;
\ No newline at end of file
+pub mod interface;
+pub mod jobs;
+pub mod train;
\ No newline at end of file
@@ -0,0 +1,31 @@
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use tokio::time::Duration;
+use crate::adapters::llm::LLMInterface;
+use crate::adapters::http::DEFAULT_MAX_RETRIES;
+use crate::cli::interface::build_provider;
+use crate::core::fine_tuning::{train_on_dataset, wait_for_job};
+use crate::utils::lib::Args;
+
+/// Fine-tunes --provider on an existing dataset, e.g. one written by `generate` and reviewed since.
+pub async fn run_train_command(args: &Args, file: PathBuf) -> Result<()> {
+    if !file.is_file() {
+        bail!("Dataset {:?} does not exist", file);
+    }
+    let fine_tune_config = args.fine_tune_config()?;
+    let llm = build_provider(args, args.provider, args.model(), DEFAULT_MAX_RETRIES)?;
+    if !llm.supports_fine_tuning() {
+        bail!("{} does not support fine-tuning", llm.name());
+    }
+
+    println!("Beginning to fine-tune the LLM on {:?}", file);
+    let job = train_on_dataset(&llm, file, &fine_tune_config).await
+        .context("Failed to fine-tune LLM")?;
+    if args.wait {
+        let job_id = job["id"].as_str().context("Failed to get fine-tuning job ID")?;
+        let job = wait_for_job(&llm, job_id, Duration::from_secs(args.poll_interval)).await
+            .context("Fine-tuning did not succeed")?;
+        println!("Fine-tuned model: {}", job["fine_tuned_model"].as_str().unwrap_or("unknown"));
+    }
+    Ok(())
+}
@@ -1,28 +1,83 @@
 use anyhow::{Context, Result};
 use std::sync::Arc;
 use std::fs::File;
-use futures::stream::{self, StreamExt};
+use futures::future;
+use futures::stream::{Stream, StreamExt};
+use std::pin::pin;
+use std::sync::Mutex as StdMutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::io::BufWriter;
 use tokio::sync::Mutex;
+use crate::adapters::input::{Chunk, InputAdapter};
 use crate::adapters::output::*;
 use crate::adapters::llm::*;
+use crate::adapters::error::{find_llm_error, LLMError};
 use crate::core::learn::*;
 use crate::core::prompts::*;
+use crate::core::report::*;
+use crate::core::manifest::RunManifest;
+use crate::core::fine_tuning::{train_on_dataset, wait_for_job, FineTuneConfig};
+use serde_json::Value;
+use tokio::time::Duration;
 use crate::utils::lib::*;
 use std::io::Write;
 use std::path::PathBuf;
 use mockall::predicate::*;
-use log::{debug, info};
+use log::{debug, info, warn};
 
 
-pub struct Assimilator<T: LLMProvider> {
+pub const DEFAULT_CONCURRENCY: usize = 10;
+/// Pairs that must complete before the failure-rate threshold can stop a run early,
+/// so a couple of early failures don't stop it. Documented with `--max-failure-rate`.
+pub const MIN_PAIRS_BEFORE_ABORT: usize = 20;
+
+pub struct Assimilator<T: LLMInterface> {
     llm: T,
-    writer: Arc<Mutex<BufWriter<File>>>
+    writer: Arc<Mutex<BufWriter<File>>>,
+    metadata_writer: Option<Arc<Mutex<BufWriter<File>>>>,
+    rejects_path: Option<PathBuf>,
+    max_failure_rate: Option<f64>,
+    manifest: Option<RunManifest>,
+    concurrency: usize,
+    fine_tune_config: FineTuneConfig
 }
 
-impl<T: LLMProvider> Assimilator<T> {
+impl<T: LLMInterface> Assimilator<T> {
     pub fn new(llm: T, writer: Arc<Mutex<BufWriter<File>>>) -> Assimilator<T> {
-        Self { llm, writer }
+        Self { llm, writer, metadata_writer: None, rejects_path: None, max_failure_rate: None, manifest: None, concurrency: DEFAULT_CONCURRENCY, fine_tune_config: FineTuneConfig::default() }
+    }
+
+    pub fn with_metadata_writer(mut self, metadata_writer: Arc<Mutex<BufWriter<File>>>) -> Self {
+        self.metadata_writer = Some(metadata_writer);
+        self
+    }
+
+    pub fn with_rejects_path(mut self, rejects_path: PathBuf) -> Self {
+        self.rejects_path = Some(rejects_path);
+        self
+    }
+
+    /// Number of chunk-prompt pairs sent to the LLM at the same time.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Record completed pairs in `manifest` and skip the pairs it already holds.
+    pub fn with_manifest(mut self, manifest: RunManifest) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    pub fn with_fine_tune_config(mut self, fine_tune_config: FineTuneConfig) -> Self {
+        self.fine_tune_config = fine_tune_config;
+        self
+    }
+
+    /// Abort `harvest` once more than this fraction (0.0 to 1.0) of completed pairs have failed.
+    pub fn with_max_failure_rate(mut self, max_failure_rate: f64) -> Self {
+        self.max_failure_rate = Some(max_failure_rate);
+        self
     }
 
     pub async fn tune_prompt(&self, use_case: &str) -> Result<Vec<String>> {
@@ -35,34 +90,146 @@ impl<T: LLMProvider> Assimilator<T> {
         Ok(response.prompts)
     }
 
-    pub async fn harvest(&self, chunk_prompt_pairs: Vec<(String, String)>) -> Result<()> {
+    /// Harvests every chunk of the input with every prompt. The input is read on a blocking thread
+    /// that stays at most `concurrency` pairs ahead of the requests.
+    pub async fn assimilate<I: InputAdapter + Send + 'static>(&self, input_adapter: I, prompts: &[String]) -> Result<HarvestReport> {
+        let prompts: Vec<Arc<str>> = prompts.iter().map(|prompt| Arc::from(prompt.as_str())).collect();
+        let chunk_prompt_pairs = stream_pairs(input_adapter, prompts, self.concurrency);
+        self.harvest(chunk_prompt_pairs).await
+    }
+
+    /// Forms an instruction for every chunk-prompt pair the stream yields. Pairs are pulled only as
+    /// in-flight requests complete, so at most `concurrency` of them are held at once.
+    /// The first input error stops the stream and is returned once in-flight pairs are done.
+    ///
+    /// Failed pairs are counted by cause in the returned report and appended to the rejects file,
+    /// if one is set, as they fail. Once `MIN_PAIRS_BEFORE_ABORT` pairs have completed, exceeding the
+    /// maximum failure rate stops the run early and the report comes back with `aborted` set.
+    pub async fn harvest<S>(&self, chunk_prompt_pairs: S) -> Result<HarvestReport>
+    where
+        S: Stream<Item = Result<(Chunk, Arc<str>)>>,
+    {
         info!("Beginning to harvest knowledge and wisdom from the input data");
-        let total_pairs = chunk_prompt_pairs.len();
-        let progress_bar = create_progress_bar(total_pairs as u64)
+        let progress_bar = create_progress_bar(0)
             .context("Failed to create progress bar")?;
+        let input_error = StdMutex::new(None);
+        let skipped = AtomicUsize::new(0);
 
-        let _results: Vec<Result<()>> = stream::iter(chunk_prompt_pairs)
-            .map(|(chunk, prompt)| {
-                let progress_bar = progress_bar.clone();
-                async move {
-                    let instruction = self.form_learning_instruction(&prompt, &chunk)
-                        .await
-                        .context("Failed to form learning instruction")?;
-
-                    output_jsonl(&self.writer, instruction)
-                        .await
-                        .context("Failed to write learning instruction to JSONL")?;
-                    progress_bar.inc(1);
-                    Ok(())
-                }
+        // Rejects of an earlier attempt at a resumed run may have been harvested since, so the file
+        // only ever lists the failures of this run
+        if let Some(rejects_path) = &self.rejects_path {
+            match std::fs::remove_file(rejects_path) {
+                Ok(()) => debug!("Removed rejects of previous run: {:?}", rejects_path),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+                Err(e) => return Err(e).with_context(|| format!("Failed to remove rejects file: {:?}", rejects_path)),
+            }
+        }
+
+        let pairs = chunk_prompt_pairs.scan((), |_, pair| {
+            future::ready(match pair {
+                Ok(pair) => {
+                    progress_bar.inc_length(1);
+                    Some(pair)
+                },
+                Err(e) => {
+                    *input_error.lock().expect("Input error lock poisoned") = Some(e);
+                    None
+                },
             })
-            .buffer_unordered(10)
-            .collect()
-            .await;
+        });
+        let pairs = pairs.filter(|(chunk, prompt)| {
+            let completed = self.manifest.as_ref()
+                .is_some_and(|manifest| manifest.is_completed(chunk, prompt));
+            if completed {
+                skipped.fetch_add(1, Ordering::Relaxed);
+                progress_bar.inc(1);
+            }
+            future::ready(!completed)
+        });
+
+        // Scoped so that an early abort drops, and thereby cancels, the in-flight requests.
+        let report = {
+            let mut results = pin!(pairs
+                .map(|(chunk, prompt)| {
+                    let progress_bar = progress_bar.clone();
+                    async move {
+                        let result = self.harvest_pair(&chunk, &prompt).await;
+                        progress_bar.inc(1);
+                        (chunk, prompt, result)
+                    }
+                })
+                .buffer_unordered(self.concurrency));
+
+            let mut report = HarvestReport::default();
+            let mut rejects_writer = None;
+            while let Some((chunk, prompt, result)) = results.next().await {
+                match result {
+                    Ok(()) => report.succeeded += 1,
+                    Err(e) => {
+                        warn!("Failed to harvest chunk {} with prompt {:?}: {:#}", chunk.hash, chunk.prompt_index, e);
+                        report.failed += 1;
+                        let cause = find_llm_error(&e).map_or("other", LLMError::kind);
+                        *report.failure_causes.entry(cause.to_string()).or_default() += 1;
+                        if let Some(rejects_path) = &self.rejects_path {
+                            if rejects_writer.is_none() {
+                                info!("Writing rejected pairs to {:?}", rejects_path);
+                                let file = File::create(rejects_path)
+                                    .with_context(|| format!("Failed to create rejects file: {:?}", rejects_path))?;
+                                rejects_writer = Some(BufWriter::new(file));
+                            }
+                            if let Some(rejects_writer) = rejects_writer.as_mut() {
+                                output_reject(rejects_writer, &chunk, &prompt, &format!("{:#}", e))
+                                    .context("Failed to write rejected pair")?;
+                            }
+                        }
+                    }
+                }
+                if self.exceeds_failure_rate(&report) && report.completed() >= MIN_PAIRS_BEFORE_ABORT {
+                    report.aborted = true;
+                    break;
+                }
+            }
+            report.skipped = skipped.load(Ordering::Relaxed);
+            report
+        };
 
         self.commit_write().await.context("Failed to commit write")?;
 
+        if let Some(e) = input_error.into_inner().expect("Input error lock poisoned") {
+            progress_bar.abandon_with_message("Failed to read input");
+            return Err(e).context("Failed to read chunk-prompt pairs from input");
+        }
+
+        if report.aborted {
+            progress_bar.abandon_with_message("Failure rate exceeded");
+            warn!("Harvest stopped early: {} of {} pairs failed", report.failed, report.completed());
+            return Ok(report);
+        }
+
         progress_bar.finish_with_message("Processing complete");
+        info!("Harvest complete: {} succeeded, {} failed, {} skipped", report.succeeded, report.failed, report.skipped);
+        Ok(report)
+    }
+
+    fn exceeds_failure_rate(&self, report: &HarvestReport) -> bool {
+        self.max_failure_rate
+            .is_some_and(|max_failure_rate| report.failure_rate() > max_failure_rate)
+    }
+
+    async fn harvest_pair(&self, chunk: &Chunk, prompt: &str) -> Result<()> {
+        let (instruction, provider) = self.form_attributed_learning_instruction(prompt, &chunk.text)
+            .await
+            .context("Failed to form learning instruction")?;
+
+        output_jsonl(&self.writer, self.metadata_writer.as_ref(), chunk, &provider, instruction)
+            .await
+            .context("Failed to write learning instruction to JSONL")?;
+
+        if let Some(manifest) = &self.manifest {
+            self.commit_write().await.context("Failed to commit write")?;
+            manifest.record(chunk, prompt).await
+                .context("Failed to record completed pair in run manifest")?;
+        }
         Ok(())
     }
 
@@ -72,18 +239,35 @@ impl<T: LLMProvider> Assimilator<T> {
             writer_guard.flush().context("Failed to flush writer")?;
         }
 
+        if let Some(metadata_writer) = &self.metadata_writer {
+            let mut writer_guard = metadata_writer.lock().await;
+            writer_guard.flush().context("Failed to flush metadata writer")?;
+        }
+
         Ok(())
     }
 
-   pub async fn train(&self, output_path: PathBuf) -> Result<()> {
+   pub async fn train(&self, output_path: PathBuf) -> Result<Value> {
     println!("Beginning to fine-tune the LLM");
-        self.llm.train(output_path).await.context("Failed to fine-tune LLM")
+        train_on_dataset(&self.llm, output_path, &self.fine_tune_config).await.context("Failed to fine-tune LLM")
+    }
+
+    /// Waits for a job returned by `train` to finish; see `fine_tuning::wait_for_job`.
+    pub async fn wait_for_training(&self, job: &Value, poll_interval: Duration) -> Result<Value> {
+        let job_id = job["id"].as_str().context("Failed to get fine-tuning job ID")?;
+        wait_for_job(&self.llm, job_id, poll_interval).await
     }
 
     pub async fn form_learning_instruction(&self, prompt: &str, chunk: &str) -> Result<Instruction> {
+        self.form_attributed_learning_instruction(prompt, chunk).await
+            .map(|(instruction, _)| instruction)
+    }
+
+    /// Forms a learning instruction along with the name of the provider that produced it.
+    pub async fn form_attributed_learning_instruction(&self, prompt: &str, chunk: &str) -> Result<(Instruction, String)> {
         let system_prompt = "You are a highly skilled finetuning expert. You are provided with a prompt and a text and you need to extract a single instruction-response pair from the text that follows the prompt.";
         let user_prompt = format!("{}\n\n{}", prompt, chunk);
-        self.llm.send_request(system_prompt, &user_prompt)
+        self.llm.send_request_attributed(system_prompt, &user_prompt)
             .await
             .context("Failed to send request to LLM for forming learning instruction")
     }
@@ -0,0 +1,262 @@
+use anyhow::{bail, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use sha2::{Digest, Sha256};
+use crate::utils::lib::{get_training_file_path, get_validation_file_path, retry};
+use clap::ValueEnum;
+use tokio::time::{sleep, Duration};
+use crate::adapters::llm::LLMInterface;
+use crate::adapters::http::DEFAULT_MAX_RETRIES;
+use log::{debug, info};
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+const TERMINAL_STATUSES: &[&str] = &["succeeded", "failed", "cancelled"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FineTuneMethod {
+    #[default]
+    Supervised,
+    /// Direct preference optimization; needs a preference dataset rather than harvested instructions
+    Dpo,
+}
+
+impl FineTuneMethod {
+    fn name(&self) -> &'static str {
+        match self {
+            FineTuneMethod::Supervised => "supervised",
+            FineTuneMethod::Dpo => "dpo",
+        }
+    }
+}
+
+/// Options for a fine-tuning job. Hyperparameters left unset are chosen by the provider.
+/// Loaded from a TOML or JSON file with `load`, using the field names below.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FineTuneConfig {
+    pub method: FineTuneMethod,
+    pub n_epochs: Option<u32>,
+    pub batch_size: Option<u32>,
+    pub learning_rate_multiplier: Option<f64>,
+    /// Weight of the penalty against diverging from the base model; DPO only
+    pub beta: Option<f64>,
+    /// Up to 64 characters added to the fine-tuned model name
+    pub suffix: Option<String>,
+    /// Also seeds the train/validation split
+    pub seed: Option<u64>,
+    /// Fraction of the dataset held out as the validation file; not sent to the provider
+    pub validation_split: Option<f64>,
+}
+
+impl FineTuneConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read fine-tuning config: {:?}", path))?;
+        let config = if path.extension().is_some_and(|extension| extension == "json") {
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse fine-tuning config: {:?}", path))?
+        } else {
+            toml::from_str(&content)
+                .with_context(|| format!("Failed to parse fine-tuning config: {:?}", path))?
+        };
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.n_epochs == Some(0) || self.batch_size == Some(0) {
+            bail!("n_epochs and batch_size must be at least 1");
+        }
+        if self.learning_rate_multiplier.is_some_and(|multiplier| multiplier <= 0.0) {
+            bail!("learning_rate_multiplier must be positive");
+        }
+        if self.beta.is_some() && self.method != FineTuneMethod::Dpo {
+            bail!("beta only applies to the dpo method");
+        }
+        if self.validation_split.is_some_and(|split| !(split > 0.0 && split < 1.0)) {
+            bail!("validation_split must be between 0.0 and 1.0, exclusive");
+        }
+        if self.suffix.as_ref().is_some_and(|suffix| suffix.chars().count() > 64) {
+            bail!("suffix must be at most 64 characters");
+        }
+        Ok(())
+    }
+
+    fn hyperparameters(&self) -> Map<String, Value> {
+        let mut hyperparameters = Map::new();
+        if let Some(n_epochs) = self.n_epochs {
+            hyperparameters.insert("n_epochs".to_string(), json!(n_epochs));
+        }
+        if let Some(batch_size) = self.batch_size {
+            hyperparameters.insert("batch_size".to_string(), json!(batch_size));
+        }
+        if let Some(learning_rate_multiplier) = self.learning_rate_multiplier {
+            hyperparameters.insert("learning_rate_multiplier".to_string(), json!(learning_rate_multiplier));
+        }
+        if let Some(beta) = self.beta {
+            hyperparameters.insert("beta".to_string(), json!(beta));
+        }
+        hyperparameters
+    }
+
+    /// Adds the options to a job creation request body. With `legacy_hyperparameters`, supervised
+    /// hyperparameters go in the top-level `hyperparameters` field older APIs expect instead of `method`.
+    pub fn apply(&self, body: &mut Value, legacy_hyperparameters: bool) {
+        let hyperparameters = self.hyperparameters();
+        if self.method == FineTuneMethod::Supervised && hyperparameters.is_empty() {
+            // Nothing to say beyond the provider's defaults
+        } else if legacy_hyperparameters && self.method == FineTuneMethod::Supervised {
+            body["hyperparameters"] = Value::Object(hyperparameters);
+        } else {
+            let method = self.method.name();
+            body["method"] = json!({"type": method, method: {"hyperparameters": hyperparameters}});
+        }
+        if let Some(suffix) = &self.suffix {
+            body["suffix"] = json!(suffix);
+        }
+        if let Some(seed) = self.seed {
+            body["seed"] = json!(seed);
+        }
+    }
+}
+
+/// Splits a JSONL dataset into training and validation files next to it, holding out
+/// `validation_split` of its lines. Lines are assigned by a seeded hash of their content, so the
+/// split only depends on the seed and the set of lines, not on the order harvesting wrote them in.
+pub fn split_dataset(path: &Path, validation_split: f64, seed: u64) -> Result<(PathBuf, PathBuf)> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open dataset: {:?}", path))?;
+    let lines: Vec<String> = BufReader::new(file).lines()
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read dataset")?
+        .into_iter()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if lines.len() < 2 {
+        bail!("Dataset {:?} has {} lines; at least 2 are needed for a validation split", path, lines.len());
+    }
+
+    let validation_count = ((lines.len() as f64 * validation_split).round() as usize).clamp(1, lines.len() - 1);
+    let mut ranked: Vec<usize> = (0..lines.len()).collect();
+    ranked.sort_by_cached_key(|&index| {
+        let mut hasher = Sha256::new();
+        hasher.update(seed.to_le_bytes());
+        hasher.update(lines[index].as_bytes());
+        (hasher.finalize(), index)
+    });
+    let validation: HashSet<usize> = ranked.into_iter().take(validation_count).collect();
+
+    let training_path = get_training_file_path(path);
+    let validation_path = get_validation_file_path(path);
+    let mut training_writer = BufWriter::new(File::create(&training_path)
+        .with_context(|| format!("Failed to create training file: {:?}", training_path))?);
+    let mut validation_writer = BufWriter::new(File::create(&validation_path)
+        .with_context(|| format!("Failed to create validation file: {:?}", validation_path))?);
+    for (index, line) in lines.iter().enumerate() {
+        let writer = if validation.contains(&index) { &mut validation_writer } else { &mut training_writer };
+        writeln!(writer, "{}", line).context("Failed to write dataset split")?;
+    }
+    training_writer.flush().context("Failed to flush training file")?;
+    validation_writer.flush().context("Failed to flush validation file")?;
+
+    info!("Split {:?} into {} training and {} validation examples", path, lines.len() - validation_count, validation_count);
+    Ok((training_path, validation_path))
+}
+
+/// Fine-tunes on a JSONL dataset, first splitting off a validation file when the config asks for one.
+pub async fn train_on_dataset<T: LLMInterface>(llm: &T, dataset_path: PathBuf, config: &FineTuneConfig) -> Result<Value> {
+    let (training_path, validation_path) = match config.validation_split {
+        Some(validation_split) => {
+            let seed = config.seed.unwrap_or_default();
+            let (training_path, validation_path) = split_dataset(&dataset_path, validation_split, seed)
+                .context("Failed to split dataset into training and validation files")?;
+            (training_path, Some(validation_path))
+        },
+        None => (dataset_path, None),
+    };
+    llm.train(training_path, validation_path, config).await
+}
+
+pub fn is_terminal(job: &Value) -> bool {
+    job["status"].as_str().is_some_and(|status| TERMINAL_STATUSES.contains(&status))
+}
+
+/// Polls a fine-tuning job until it succeeds, fails or is cancelled. New events are printed as they
+/// arrive, and metrics events drive a progress bar over the training steps showing the latest loss.
+/// Returns the finished job, or an error naming the reason if it did not succeed.
+pub async fn wait_for_job<T: LLMInterface>(llm: &T, job_id: &str, poll_interval: Duration) -> Result<Value> {
+    let progress_bar = ProgressBar::new_spinner();
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+            .context("Failed to create progress bar style")?
+            .progress_chars("#>-"),
+    );
+    progress_bar.enable_steady_tick(Duration::from_millis(250));
+
+    let mut seen_events = HashSet::new();
+    let job = loop {
+        // A job runs for hours, so a transient error while polling is retried instead of ending the wait
+        let events = retry(DEFAULT_MAX_RETRIES, poll_interval, || llm.list_fine_tuning_events(job_id)).await
+            .context("Failed to list fine-tuning events")?;
+        let events = events["data"].as_array().cloned().unwrap_or_default();
+        // Events come newest first
+        for event in events.iter().rev() {
+            let Some(id) = event["id"].as_str() else { continue };
+            if !seen_events.insert(id.to_string()) {
+                continue;
+            }
+            show_event(&progress_bar, event);
+        }
+
+        let job = retry(DEFAULT_MAX_RETRIES, poll_interval, || llm.get_fine_tuning_job(job_id)).await
+            .context("Failed to get fine-tuning job")?;
+        debug!("Fine-tuning job {} status: {:?}", job_id, job["status"]);
+        if is_terminal(&job) {
+            break job;
+        }
+        if progress_bar.length().is_none() {
+            progress_bar.set_message(format!("Job {}", job["status"].as_str().unwrap_or("pending")));
+        }
+        sleep(poll_interval).await;
+    };
+
+    match job["status"].as_str() {
+        Some("succeeded") => {
+            progress_bar.finish_with_message("Fine-tuning complete");
+            Ok(job)
+        },
+        status => {
+            progress_bar.abandon_with_message(format!("Fine-tuning {}", status.unwrap_or("stopped")));
+            let reason = job["error"]["message"].as_str().unwrap_or("no reason given");
+            bail!("Fine-tuning job {} {}: {}", job_id, status.unwrap_or("stopped"), reason)
+        }
+    }
+}
+
+fn show_event(progress_bar: &ProgressBar, event: &Value) {
+    let data = &event["data"];
+    if event["type"] == "metrics" {
+        if let (Some(step), Some(total_steps)) = (data["step"].as_u64(), data["total_steps"].as_u64()) {
+            progress_bar.set_length(total_steps);
+            progress_bar.set_position(step);
+        }
+        if let Some(train_loss) = data["train_loss"].as_f64() {
+            progress_bar.set_message(format!("training loss {:.4}", train_loss));
+            if progress_bar.is_hidden() {
+                println!("Step {}/{}: training loss {:.4}", data["step"], data["total_steps"], train_loss);
+            }
+        }
+        return;
+    }
+    if let Some(message) = event["message"].as_str() {
+        // Printed outside the bar so events also reach logs when stdout is not a terminal
+        progress_bar.suspend(|| println!("[{}] {}", event["level"].as_str().unwrap_or("info"), message));
+    }
+}
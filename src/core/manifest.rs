@@ -0,0 +1,143 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+use crate::adapters::input::Chunk;
+use log::{info, warn};
+
+#[derive(Serialize, Deserialize)]
+struct ManifestLine {
+    key: String,
+    source: Option<PathBuf>,
+    chunk: String,
+    prompt_index: Option<usize>,
+}
+
+/// Append-only record of the chunk-prompt pairs a run has completed, keyed by source file, content
+/// hash and prompt, so that an interrupted run can be resumed without repeating finished pairs.
+pub struct RunManifest {
+    path: PathBuf,
+    completed: HashSet<String>,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl RunManifest {
+    /// Starts a new manifest, replacing any existing file at `path`.
+    pub fn create(path: PathBuf) -> Result<Self> {
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create run manifest: {:?}", path))?;
+        Ok(Self { path, completed: HashSet::new(), writer: Mutex::new(BufWriter::new(file)) })
+    }
+
+    /// Loads the pairs recorded in an existing manifest and appends new ones to it.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        truncate_partial_line(&path)?;
+        let file = File::open(&path)
+            .with_context(|| format!("Failed to open run manifest: {:?}", path))?;
+        let mut completed = HashSet::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.context("Failed to read run manifest")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: ManifestLine = serde_json::from_str(&line)
+                .context("Failed to parse run manifest entry")?;
+            completed.insert(entry.key);
+        }
+        info!("Loaded {} completed pairs from run manifest {:?}", completed.len(), path);
+
+        let file = OpenOptions::new().append(true).open(&path)
+            .with_context(|| format!("Failed to open run manifest for appending: {:?}", path))?;
+        Ok(Self { path, completed, writer: Mutex::new(BufWriter::new(file)) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The source is part of the key, as the same text in two files is two pairs to harvest.
+    pub fn pair_key(chunk: &Chunk, prompt: &str) -> String {
+        let mut hasher = Sha256::new();
+        if let Some(source) = &chunk.source {
+            hasher.update(source.as_os_str().as_encoded_bytes());
+        }
+        hasher.update([0]);
+        hasher.update(chunk.hash.as_bytes());
+        hasher.update([0]);
+        hasher.update(prompt.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    pub fn is_completed(&self, chunk: &Chunk, prompt: &str) -> bool {
+        self.completed.contains(&Self::pair_key(chunk, prompt))
+    }
+
+    /// Records a completed pair. Callers must flush the pair's output first, so that the manifest
+    /// never claims a pair whose instruction was lost.
+    pub async fn record(&self, chunk: &Chunk, prompt: &str) -> Result<()> {
+        let line = ManifestLine {
+            key: Self::pair_key(chunk, prompt),
+            source: chunk.source.clone(),
+            chunk: chunk.hash.clone(),
+            prompt_index: chunk.prompt_index,
+        };
+        let json = serde_json::to_string(&line)
+            .context("Failed to serialize run manifest entry")?;
+        let mut writer = self.writer.lock().await;
+        writeln!(writer, "{}", json)
+            .context("Failed to write run manifest entry")?;
+        writer.flush().context("Failed to flush run manifest")?;
+        Ok(())
+    }
+}
+
+pub fn save_prompts(path: &Path, prompts: &[String]) -> Result<()> {
+    let json = serde_json::to_string_pretty(prompts)
+        .context("Failed to serialize prompts")?;
+    fs::write(path, json)
+        .with_context(|| format!("Failed to write prompts file: {:?}", path))
+}
+
+pub fn load_prompts(path: &Path) -> Result<Vec<String>> {
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read prompts file: {:?}", path))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse prompts file: {:?}", path))
+}
+
+/// Drops a trailing line left incomplete by a crash, so that appended lines start on a fresh line.
+pub fn truncate_partial_line(path: &Path) -> Result<()> {
+    let content = fs::read(path)
+        .with_context(|| format!("Failed to read file: {:?}", path))?;
+    if content.is_empty() || content.ends_with(b"\n") {
+        return Ok(());
+    }
+    let keep = content.iter().rposition(|&byte| byte == b'\n').map_or(0, |index| index + 1);
+    warn!("Dropping incomplete last line of {:?}", path);
+    let file = OpenOptions::new().write(true).open(path)
+        .with_context(|| format!("Failed to open file: {:?}", path))?;
+    file.set_len(keep as u64)
+        .with_context(|| format!("Failed to truncate file: {:?}", path))
+}
+
+
+/// Counts the complete, newline-terminated lines of a file without reading it into memory.
+pub fn count_lines(path: &Path) -> Result<usize> {
+    let mut reader = BufReader::new(File::open(path)
+        .with_context(|| format!("Failed to open file: {:?}", path))?);
+    let mut lines = 0;
+    loop {
+        let buffer = reader.fill_buf()
+            .with_context(|| format!("Failed to read file: {:?}", path))?;
+        if buffer.is_empty() {
+            return Ok(lines);
+        }
+        lines += buffer.iter().filter(|&&byte| byte == b'\n').count();
+        let length = buffer.len();
+        reader.consume(length);
+    }
+}
\ No newline at end of file
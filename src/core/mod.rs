@@ -1,3 +1,6 @@
 pub mod learn;
 pub mod prompts;
-pub mod assimilator;
\ No newline at end of file
+pub mod assimilator;
+pub mod report;
+pub mod manifest;
+pub mod fine_tuning;
@@ -0,0 +1,27 @@
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default)]
+pub struct HarvestReport {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Pairs skipped because the run manifest already records them as completed.
+    pub skipped: usize,
+    /// Number of failed pairs by cause, e.g. `rate_limited`; the pairs themselves are in the rejects file.
+    pub failure_causes: BTreeMap<String, usize>,
+    /// Set when the run stopped early because the failure rate exceeded the configured threshold.
+    pub aborted: bool,
+}
+
+impl HarvestReport {
+    pub fn completed(&self) -> usize {
+        self.succeeded + self.failed
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        if self.completed() == 0 {
+            0.0
+        } else {
+            self.failed as f64 / self.completed() as f64
+        }
+    }
+}
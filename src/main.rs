@@ -3,6 +3,7 @@ use lib::cli::interface::run_cli_interface;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     run_cli_interface().await?;
     Ok(())
 }
\ No newline at end of file
@@ -2,7 +2,7 @@ use anyhow::{Context, Result};
 use std::io::{self, Write};
 use crate::core::prompts::generate_prompts;
 use crate::core::assimilator::Assimilator;
-use crate::adapters::llm::LLMProvider;
+use crate::adapters::llm::LLMInterface;
 
 pub fn input_use_case_option() -> Result<usize> {
     loop {
@@ -46,7 +46,7 @@ pub fn input_use_case_custom() -> Result<String> {
     }
 }
 
-pub async fn fetch_prompts<T: LLMProvider>(assimilator: &Assimilator<T>) -> Result<Vec<String>> {
+pub async fn fetch_prompts<T: LLMInterface>(assimilator: &Assimilator<T>) -> Result<Vec<String>> {
     let use_case_option = input_use_case_option()
         .context("Failed to get use case option")?;
 
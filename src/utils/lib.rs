@@ -1,35 +1,301 @@
 use indicatif::{ProgressBar, ProgressStyle};
 use std::io;
-use clap::Parser;
-use std::path::PathBuf;
-use std::fs::create_dir_all;
-use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::fs::{self, create_dir_all};
+use anyhow::{anyhow, Context, Result};
 
 use chrono::Local;
 
-use crate::adapters::input::FileInputAdapter;
+use crate::adapters::input::{default_include_patterns, Chunk, ChunkUnit, InputAdapter, Splitter};
 use tokio::time::{sleep, Duration};
 use std::future::Future;
+use rand::Rng;
+use log::warn;
+use crate::adapters::error::{find_llm_error, LLMError};
+use crate::adapters::llm::Provider;
+use crate::adapters::cache::CacheMode;
+use crate::core::assimilator::DEFAULT_CONCURRENCY;
+use crate::core::fine_tuning::{FineTuneConfig, FineTuneMethod, DEFAULT_POLL_INTERVAL};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::sync::Arc;
+use futures::stream::{self, Stream};
+use tokio::sync::mpsc;
+use std::panic::{self, AssertUnwindSafe};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
-    #[clap(short, long, value_parser, default_value = "./input")]
+    #[clap(short, long, value_parser, default_value = "./input", global = true)]
     pub input: PathBuf,
 
-    #[clap(short, long, value_parser)]
+    #[clap(short, long, value_parser, global = true)]
     pub output: Option<PathBuf>,
 
-    #[clap(long, default_value = "10000")]
+    #[clap(long, default_value = "10000", global = true)]
     pub chunk_size: usize,
 
-    #[clap(long, default_value = "gpt-4o-mini-2024-07-18")]
-    pub model: String,
+    /// Whether --chunk-size counts characters or tokens of the --model tokenizer
+    #[clap(long, value_enum, default_value = "chars", global = true)]
+    pub chunk_unit: ChunkUnit,
 
-    #[clap(long, default_value = "Creative writing")]
+    /// Amount of text, in --chunk-unit, repeated between consecutive chunks
+    #[clap(long, default_value = "0", global = true)]
+    pub chunk_overlap: usize,
+
+    #[clap(long, value_enum, default_value = "auto", global = true)]
+    pub splitter: Splitter,
+
+    /// Descend into subdirectories of the input folder
+    #[clap(short, long, global = true)]
+    pub recursive: bool,
+
+    /// Glob patterns (relative to the input folder) of files to read
+    #[clap(long, value_delimiter = ',', default_values_t = default_include_patterns(), global = true)]
+    pub include: Vec<String>,
+
+    /// Glob patterns (relative to the input folder) of files to skip
+    #[clap(long, value_delimiter = ',', global = true)]
+    pub exclude: Vec<String>,
+
+    /// Follow symbolic links while reading the input folder
+    #[clap(long, global = true)]
+    pub follow_symlinks: bool,
+
+    /// Skip files larger than this many bytes
+    #[clap(long, global = true)]
+    pub max_file_size: Option<u64>,
+
+    /// Write chunk provenance for every dataset line to a `.meta.jsonl` sidecar next to the output
+    #[clap(long, global = true)]
+    pub metadata: bool,
+
+    /// Continue an interrupted run: skip the pairs its run manifest records as done and append to its output
+    #[clap(long, global = true)]
+    pub resume: bool,
+
+    /// Fail the run when more than this fraction (0.0 to 1.0) of chunk-prompt pairs failed. Once 20 pairs
+    /// have completed, the run also stops early as soon as the fraction is exceeded
+    #[clap(long, value_parser = parse_failure_rate, global = true)]
+    pub max_failure_rate: Option<f64>,
+
+    /// Number of chunk-prompt pairs sent to the LLM at the same time
+    #[clap(long, default_value_t = DEFAULT_CONCURRENCY, global = true)]
+    pub concurrency: usize,
+
+    /// Requests-per-minute budget for LLM requests
+    #[clap(long, global = true)]
+    pub requests_per_minute: Option<u32>,
+
+    /// Tokens-per-minute budget for LLM requests
+    #[clap(long, global = true)]
+    pub tokens_per_minute: Option<u32>,
+
+    /// LLM provider used to generate instructions and fine-tune
+    #[clap(long, value_enum, default_value_t = Provider::OpenAI, global = true)]
+    pub provider: Provider,
+
+    /// Base URL of an OpenAI-compatible API, e.g. http://localhost:11434/v1 for Ollama, or the resource endpoint for azure
+    #[clap(long, global = true)]
+    pub base_url: Option<String>,
+
+    /// Environment variable holding the API key; defaults to the provider's usual variable, e.g. OPENAI_API_KEY
+    #[clap(long, global = true)]
+    pub api_key_env: Option<String>,
+
+    /// Azure deployment that serves --model; defaults to the model name
+    #[clap(long, global = true)]
+    pub deployment: Option<String>,
+
+    /// Azure OpenAI API version
+    #[clap(long, default_value = "2024-10-21", global = true)]
+    pub api_version: String,
+
+    /// Extra header sent with every request to the OpenAI-compatible API, as "Name: value"; repeatable
+    #[clap(long = "header", value_parser = parse_header, global = true)]
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// TOML or JSON file with fine-tuning options; the flags below override it
+    #[clap(long, global = true)]
+    pub fine_tune_config: Option<PathBuf>,
+
+    /// Fine-tuning method
+    #[clap(long, value_enum, global = true)]
+    pub method: Option<FineTuneMethod>,
+
+    /// Number of epochs to train for; chosen by the provider when unset
+    #[clap(long, global = true)]
+    pub n_epochs: Option<u32>,
+
+    /// Training batch size; chosen by the provider when unset
+    #[clap(long, global = true)]
+    pub batch_size: Option<u32>,
+
+    /// Learning rate multiplier; chosen by the provider when unset
+    #[clap(long, global = true)]
+    pub learning_rate_multiplier: Option<f64>,
+
+    /// DPO beta; only with --method dpo
+    #[clap(long, global = true)]
+    pub beta: Option<f64>,
+
+    /// Up to 64 characters added to the fine-tuned model name
+    #[clap(long, global = true)]
+    pub suffix: Option<String>,
+
+    /// Seed for reproducible fine-tuning and train/validation split
+    #[clap(long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Fraction of the dataset held out as a validation file, e.g. 0.1
+    #[clap(long, global = true)]
+    pub validation_split: Option<f64>,
+
+    /// Harvest without fine-tuning, like the generate command
+    #[clap(long, global = true)]
+    pub no_train: bool,
+
+    /// Wait for the fine-tuning job to finish, showing its progress and training loss
+    #[clap(long, global = true)]
+    pub wait: bool,
+
+    /// Seconds between fine-tuning job status checks with --wait
+    #[clap(long, default_value_t = DEFAULT_POLL_INTERVAL.as_secs(), value_parser = clap::value_parser!(u64).range(1..), global = true)]
+    pub poll_interval: u64,
+
+    /// Directory for the on-disk cache of LLM responses; caching is off when unset
+    #[clap(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// How --cache-dir is used
+    #[clap(long, value_enum, default_value_t = CacheMode::ReadWrite, global = true)]
+    pub cache_mode: CacheMode,
+
+    /// Spread requests over several providers, as provider[:model][=weight]; repeatable.
+    /// Rate-limited or failing providers fail over to the next one
+    #[clap(long = "route", value_parser = parse_route, global = true)]
+    pub routes: Vec<Route>,
+
+    /// Model to use; defaults to gpt-4o-mini-2024-07-18, or claude-3-5-haiku-latest and gemini-2.0-flash for those providers
+    #[clap(long, global = true)]
+    pub model: Option<String>,
+
+    #[clap(long, default_value = "Creative writing", global = true)]
     pub use_case: String,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Harvest a dataset and fine-tune on it; the default when no command is given
+    Run,
+    /// Harvest a dataset without fine-tuning, so it can be reviewed before `train`
+    Generate,
+    /// Fine-tune --provider on an existing JSONL dataset
+    Train {
+        file: PathBuf,
+    },
+    /// Manage fine-tuning jobs of --provider
+    Jobs {
+        #[clap(subcommand)]
+        action: JobsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum JobsCommand {
+    /// List recent fine-tuning jobs
+    List {
+        /// Number of jobs to list
+        #[clap(long, default_value = "20")]
+        limit: u32,
+    },
+    /// Show a fine-tuning job
+    Show { id: String },
+    /// Cancel a running fine-tuning job
+    Cancel { id: String },
+    /// List the checkpoints saved during a fine-tuning job
+    Checkpoints { id: String },
+}
+
+impl Args {
+    pub fn model(&self) -> String {
+        self.model.clone().unwrap_or_else(|| self.provider.default_model().to_string())
+    }
+
+    /// Whether a harvest run goes on to fine-tune.
+    pub fn should_train(&self) -> bool {
+        !self.no_train && !matches!(self.command, Some(Command::Generate))
+    }
+
+    /// The --fine-tune-config file, if any, overridden by the fine-tuning flags.
+    pub fn fine_tune_config(&self) -> Result<FineTuneConfig> {
+        let mut config = match &self.fine_tune_config {
+            Some(path) => FineTuneConfig::load(path)?,
+            None => FineTuneConfig::default(),
+        };
+        if let Some(method) = self.method {
+            config.method = method;
+        }
+        config.n_epochs = self.n_epochs.or(config.n_epochs);
+        config.batch_size = self.batch_size.or(config.batch_size);
+        config.learning_rate_multiplier = self.learning_rate_multiplier.or(config.learning_rate_multiplier);
+        config.beta = self.beta.or(config.beta);
+        config.suffix = self.suffix.clone().or(config.suffix);
+        config.seed = self.seed.or(config.seed);
+        config.validation_split = self.validation_split.or(config.validation_split);
+        config.validate().context("Invalid fine-tuning config")?;
+        Ok(config)
+    }
+
+    pub fn extra_headers(&self) -> HeaderMap {
+        self.headers.iter().cloned().collect()
+    }
+}
+
+fn parse_failure_rate(value: &str) -> Result<f64, String> {
+    let rate: f64 = value.parse().map_err(|_| format!("{} is not a number", value))?;
+    if (0.0..=1.0).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(format!("{} is not between 0.0 and 1.0", rate))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub provider: Provider,
+    pub model: Option<String>,
+    pub weight: u32,
 }
 
+fn parse_route(value: &str) -> Result<Route, String> {
+    let (route, weight) = match value.rsplit_once('=') {
+        Some((route, weight)) => {
+            let weight = weight.parse().map_err(|_| format!("{} is not a valid weight", weight))?;
+            (route, weight)
+        },
+        None => (value, 1),
+    };
+    let (provider, model) = match route.split_once(':') {
+        Some((provider, model)) => (provider, Some(model.to_string())),
+        None => (route, None),
+    };
+    let provider = Provider::from_str(provider, true)?;
+    Ok(Route { provider, model, weight })
+}
+
+fn parse_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, header_value) = value.split_once(':')
+        .ok_or_else(|| format!("{} is not in the form \"Name: value\"", value))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|e| format!("Invalid header name {}: {}", name.trim(), e))?;
+    let header_value = HeaderValue::from_str(header_value.trim())
+        .map_err(|e| format!("Invalid value for header {}: {}", name, e))?;
+    Ok((name, header_value))
+}
 
 pub trait ToVecString {
     fn to_vec_string<V: FromIterator<String>>(&self) -> V;
@@ -66,38 +332,140 @@ pub fn get_output_file_path(output_arg: Option<PathBuf>) -> PathBuf {
 }
 
 
-pub fn create_pairs(input: PathBuf, chunk_size: usize, prompts: &[String]) -> Result<Vec<(String, String)>> {
-    let input_adapter = FileInputAdapter::new(input, chunk_size);
-    let chunks = input_adapter.fetch_chunks()
-        .context("Failed to fetch chunks from input")?;
-    Ok(create_chunk_prompt_pairs(&chunks, prompts))
+/// Finds the output file of the run to resume: `output_arg` itself if it is a file, otherwise the
+/// most recent output in the directory (default `./output`) that has a run manifest.
+pub fn get_resume_file_path(output_arg: Option<PathBuf>) -> Result<PathBuf> {
+    let output_dir = match output_arg {
+        Some(path) if path.is_file() => return Ok(path),
+        Some(path) if path.is_dir() => path,
+        Some(path) => anyhow::bail!("No output to resume at {:?}", path),
+        None => PathBuf::from("./output"),
+    };
+
+    let mut candidates = Vec::new();
+    for entry in fs::read_dir(&output_dir)
+        .with_context(|| format!("Failed to read output directory: {:?}", output_dir))?
+    {
+        let path = entry.context("Failed to read directory entry")?.path();
+        if get_manifest_file_path(&path).is_file() && path.extension().is_some_and(|extension| extension == "jsonl") {
+            candidates.push(path);
+        }
+    }
+    // Output files are named by timestamp, so the last one in name order is the most recent run
+    candidates.sort();
+    candidates.pop()
+        .with_context(|| format!("No resumable run found in {:?}", output_dir))
+}
+
+pub fn get_metadata_file_path(output_path: &Path) -> PathBuf {
+    get_sidecar_file_path(output_path, "meta.jsonl")
+}
+
+pub fn get_rejects_file_path(output_path: &Path) -> PathBuf {
+    get_sidecar_file_path(output_path, "rejects.jsonl")
+}
+
+pub fn get_manifest_file_path(output_path: &Path) -> PathBuf {
+    get_sidecar_file_path(output_path, "manifest.jsonl")
+}
+
+pub fn get_prompts_file_path(output_path: &Path) -> PathBuf {
+    get_sidecar_file_path(output_path, "prompts.json")
+}
+
+pub fn get_training_file_path(output_path: &Path) -> PathBuf {
+    get_sidecar_file_path(output_path, "train.jsonl")
+}
+
+pub fn get_validation_file_path(output_path: &Path) -> PathBuf {
+    get_sidecar_file_path(output_path, "validation.jsonl")
+}
+
+fn get_sidecar_file_path(output_path: &Path, suffix: &str) -> PathBuf {
+    let stem = output_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("output");
+    output_path.with_file_name(format!("{}.{}", stem, suffix))
+}
+
+/// Pairs every chunk of the input with every prompt. Files are read, parsed and split on a blocking
+/// thread that feeds a channel of `buffer` pairs, so parsing a large document does not stall the
+/// requests in flight, and memory use is bounded by the buffer. The thread stops once the stream
+/// is dropped.
+pub fn stream_pairs<I: InputAdapter + Send + 'static>(
+    input_adapter: I,
+    prompts: Vec<Arc<str>>,
+    buffer: usize
+) -> impl Stream<Item = Result<(Chunk, Arc<str>)>> {
+    let (sender, receiver) = mpsc::channel(buffer.max(1));
+    tokio::task::spawn_blocking(move || {
+        let produced = panic::catch_unwind(AssertUnwindSafe(|| send_pairs(&input_adapter, &prompts, &sender)));
+        if produced.is_err() {
+            let _ = sender.blocking_send(Err(anyhow!("Reading input panicked")));
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|pair| (pair, receiver))
+    })
 }
+
+fn send_pairs<I: InputAdapter>(input_adapter: &I, prompts: &[Arc<str>], sender: &mpsc::Sender<Result<(Chunk, Arc<str>)>>) {
+    let chunks = match input_adapter.chunks().context("Failed to fetch chunks from input") {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            let _ = sender.blocking_send(Err(e));
+            return;
+        },
+    };
+    for chunk in chunks {
+        let pairs = match chunk {
+            Ok(chunk) => create_chunk_prompt_pairs(std::slice::from_ref(&chunk), prompts)
+                .into_iter()
+                .map(Ok)
+                .collect(),
+            Err(e) => vec![Err(e)],
+        };
+        for pair in pairs {
+            // Fails once the consumer has dropped the stream, e.g. after an aborted harvest
+            if sender.blocking_send(pair).is_err() {
+                return;
+            }
+        }
+    }
+}
+
 pub fn create_progress_bar(total: u64) -> Result<ProgressBar, io::Error> {
     let pb = ProgressBar::new(total);
     pb.set_style(
         ProgressStyle::default_bar()
             .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+            .map_err(io::Error::other)?
             .progress_chars("#>-"),
     );
     Ok(pb)
 }
 
 pub fn create_chunk_prompt_pairs(
-    chunks: &Vec<String>,
-    prompts: &[String]
-) -> Vec<(String, String)> {
+    chunks: &[Chunk],
+    prompts: &[Arc<str>]
+) -> Vec<(Chunk, Arc<str>)> {
     chunks
-        .into_iter()
+        .iter()
         .flat_map(|chunk| {
             prompts
                 .iter()
-                .map(move |prompt| (chunk.clone(), prompt.to_owned()))
+                .enumerate()
+                .map(move |(prompt_index, prompt)| {
+                    let mut chunk = chunk.clone();
+                    chunk.prompt_index = Some(prompt_index);
+                    (chunk, Arc::clone(prompt))
+                })
         })
         .collect()
 }
 
 
+/// Runs `task` until it succeeds, retrying only errors classified as retryable by `LLMError`.
+/// Waits for the provider's `Retry-After` when it gives one, and otherwise backs off exponentially
+/// from `initial_delay`. Jitter is added to every wait so concurrent requests don't retry in lockstep.
 pub async fn retry<F, Fut, T>(
     max_retries: u32,
     initial_delay: Duration,
@@ -113,15 +481,28 @@ where
     loop {
         match task().await {
             Ok(result) => return Ok(result),
-            Err(e) if retries >= max_retries => {
-                return Err(e).context(format!("Task failed after {} retries", max_retries))
-            },
-            Err(_) => {
-                println!("Task failed. Retrying in {:?}...", delay);
-                sleep(delay).await;
+            Err(e) => {
+                let llm_error = find_llm_error(&e);
+                if !llm_error.is_some_and(LLMError::is_retryable) {
+                    return Err(e);
+                }
+                if retries >= max_retries {
+                    return Err(e).context(format!("Task failed after {} retries", max_retries));
+                }
+
+                let wait = match llm_error.and_then(LLMError::retry_after) {
+                    Some(retry_after) => retry_after + jitter(Duration::from_millis(250)),
+                    None => delay + jitter(delay / 2),
+                };
+                warn!("Task failed, retrying in {:?} ({}/{}): {:#}", wait, retries + 1, max_retries, e);
+                sleep(wait).await;
                 retries += 1;
                 delay *= 2; // Exponential backoff
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn jitter(max: Duration) -> Duration {
+    max.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+}
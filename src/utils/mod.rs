@@ -1,3 +1,4 @@
 pub mod llm;
 pub mod input;
-pub mod lib;
\ No newline at end of file
+pub mod lib;
+pub mod rate_limit;
\ No newline at end of file
@@ -0,0 +1,213 @@
+use reqwest::header::HeaderMap;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Duration, Instant};
+use log::{debug, warn};
+use chrono::{DateTime, Utc};
+
+/// Lowest fraction of the configured budgets the limiter will back off to.
+const MIN_SCALE: f64 = 0.1;
+/// How much of the budget each successful request wins back after a 429.
+const RECOVERY_STEP: f64 = 0.01;
+const DEFAULT_RATE_LIMIT_PAUSE: Duration = Duration::from_secs(1);
+
+struct Bucket {
+    per_minute: f64,
+    available: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u32) -> Self {
+        Self { per_minute: per_minute as f64, available: per_minute as f64, last_refill: Instant::now() }
+    }
+
+    fn refill(&mut self, scale: f64, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_minute * scale / 60.0).min(self.per_minute * scale);
+        self.last_refill = now;
+    }
+
+    /// Time until `amount` is available, or `None` if it already is. Requests larger than
+    /// the whole budget only wait for a full bucket.
+    fn wait_for(&self, amount: f64, scale: f64) -> Option<Duration> {
+        let amount = amount.min(self.per_minute * scale);
+        if self.available >= amount {
+            None
+        } else {
+            Some(Duration::from_secs_f64((amount - self.available) * 60.0 / (self.per_minute * scale)))
+        }
+    }
+}
+
+struct State {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    paused_until: Option<Instant>,
+    scale: f64,
+}
+
+/// Spreads requests over requests-per-minute and tokens-per-minute budgets, shared by every
+/// concurrent request to a provider. It backs off when the provider answers 429 or reports an
+/// exhausted quota in its rate-limit headers, and recovers gradually as requests succeed.
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                requests: requests_per_minute.map(Bucket::new),
+                tokens: tokens_per_minute.map(Bucket::new),
+                paused_until: None,
+                scale: 1.0,
+            }),
+        }
+    }
+
+    /// Waits until one request using `tokens` tokens fits the budgets, then reserves it.
+    pub async fn acquire(&self, tokens: u32) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let now = Instant::now();
+                let scale = state.scale;
+                if let Some(bucket) = state.requests.as_mut() {
+                    bucket.refill(scale, now);
+                }
+                if let Some(bucket) = state.tokens.as_mut() {
+                    bucket.refill(scale, now);
+                }
+
+                let paused = state.paused_until
+                    .filter(|until| *until > now)
+                    .map(|until| until - now);
+                let wait = paused
+                    .or_else(|| state.requests.as_ref().and_then(|bucket| bucket.wait_for(1.0, scale)))
+                    .or_else(|| state.tokens.as_ref().and_then(|bucket| bucket.wait_for(tokens as f64, scale)));
+
+                if wait.is_none() {
+                    if let Some(bucket) = state.requests.as_mut() {
+                        bucket.available -= 1.0;
+                    }
+                    if let Some(bucket) = state.tokens.as_mut() {
+                        bucket.available -= (tokens as f64).min(bucket.per_minute * scale);
+                    }
+                }
+                wait
+            };
+
+            match wait {
+                Some(wait) => {
+                    debug!("Rate limit reached, waiting {:?}", wait);
+                    sleep(wait).await;
+                },
+                None => return,
+            }
+        }
+    }
+
+    /// Returns tokens reserved by `acquire` that the request turned out not to use, or takes the
+    /// tokens it used beyond the reservation. `reserved` is what was passed to `acquire`, which
+    /// reserves at most the whole budget.
+    pub async fn settle(&self, reserved: u32, used: u32) {
+        let mut state = self.state.lock().await;
+        let scale = state.scale;
+        if let Some(bucket) = state.tokens.as_mut() {
+            let capacity = bucket.per_minute * scale;
+            let reserved = (reserved as f64).min(capacity);
+            bucket.available = (bucket.available + reserved - used as f64).min(capacity);
+        }
+    }
+
+    pub async fn on_success(&self) {
+        let mut state = self.state.lock().await;
+        state.scale = (state.scale + RECOVERY_STEP).min(1.0);
+    }
+
+    /// Pauses all requests and halves the budgets after the provider rejected a request with 429.
+    pub async fn on_rate_limited(&self, retry_after: Option<Duration>) {
+        let mut state = self.state.lock().await;
+        state.scale = (state.scale / 2.0).max(MIN_SCALE);
+        let pause = retry_after.unwrap_or(DEFAULT_RATE_LIMIT_PAUSE);
+        let until = Instant::now() + pause;
+        state.paused_until = Some(state.paused_until.map_or(until, |paused_until| paused_until.max(until)));
+        warn!("Rate limited by provider, pausing for {:?} and scaling budgets to {:.0}%", pause, state.scale * 100.0);
+    }
+
+    /// Syncs the budgets with OpenAI's `x-ratelimit-*` or Anthropic's `anthropic-ratelimit-*` headers,
+    /// pausing until the reset time when the provider reports a budget as used up.
+    pub async fn observe_headers(&self, headers: &HeaderMap) {
+        let mut guard = self.state.lock().await;
+        let state = &mut *guard;
+        let now = Instant::now();
+        for (kind, bucket) in [("requests", state.requests.as_mut()), ("tokens", state.tokens.as_mut())] {
+            let Some((remaining, reset)) = openai_rate_limit(headers, kind)
+                .or_else(|| anthropic_rate_limit(headers, kind)) else {
+                continue;
+            };
+            if let Some(bucket) = bucket {
+                bucket.available = bucket.available.min(remaining);
+            }
+            if remaining < 1.0 {
+                let reset = reset.unwrap_or(DEFAULT_RATE_LIMIT_PAUSE);
+                let until = now + reset;
+                state.paused_until = Some(state.paused_until.map_or(until, |paused_until| paused_until.max(until)));
+                debug!("Provider reports no {} remaining, pausing for {:?}", kind, reset);
+            }
+        }
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok().map(str::trim)
+}
+
+/// Remaining budget and time to reset from `x-ratelimit-remaining-*` and `x-ratelimit-reset-*` (e.g. `6m0s`).
+fn openai_rate_limit(headers: &HeaderMap, kind: &str) -> Option<(f64, Option<Duration>)> {
+    let remaining = header_str(headers, &format!("x-ratelimit-remaining-{}", kind))?.parse().ok()?;
+    let reset = header_str(headers, &format!("x-ratelimit-reset-{}", kind)).and_then(parse_reset_duration);
+    Some((remaining, reset))
+}
+
+/// Remaining budget and time to reset from `anthropic-ratelimit-*-remaining` and the RFC 3339 `*-reset` time.
+fn anthropic_rate_limit(headers: &HeaderMap, kind: &str) -> Option<(f64, Option<Duration>)> {
+    let remaining = header_str(headers, &format!("anthropic-ratelimit-{}-remaining", kind))?.parse().ok()?;
+    let reset = header_str(headers, &format!("anthropic-ratelimit-{}-reset", kind))
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .and_then(|reset| (reset.with_timezone(&Utc) - Utc::now()).to_std().ok());
+    Some((remaining, reset))
+}
+
+/// Parses reset durations such as `1s`, `250ms`, `6m0s` or `1h2m3.5s`.
+pub fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut chars = value.trim().chars().peekable();
+    let mut parsed_any = false;
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let amount: f64 = number.parse().ok()?;
+        number.clear();
+        let seconds = match c {
+            'h' => amount * 3600.0,
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                amount / 1000.0
+            },
+            'm' => amount * 60.0,
+            's' => amount,
+            _ => return None,
+        };
+        total += seconds;
+        parsed_any = true;
+    }
+    if !number.is_empty() {
+        total += number.parse::<f64>().ok()?;
+        parsed_any = true;
+    }
+    parsed_any.then(|| Duration::from_secs_f64(total))
+}
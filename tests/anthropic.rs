@@ -0,0 +1,39 @@
+use lib::adapters::anthropic::Anthropic;
+use lib::adapters::llm::{LLMProvider, OutputFormat};
+use lib::utils::llm::INSTRUCTION_RESPONSE_FORMAT;
+
+fn anthropic() -> Anthropic {
+    Anthropic::new("claude-3-5-haiku-latest".to_string(), 0.5, 1024)
+}
+
+#[test]
+fn sends_the_system_prompt_separately() {
+    let body = anthropic().generate_request_body("system", "user", &OutputFormat::String).unwrap();
+
+    assert_eq!(body["model"], "claude-3-5-haiku-latest");
+    assert_eq!(body["system"], "system");
+    assert_eq!(body["messages"], serde_json::json!([{"role": "user", "content": "user"}]));
+    assert_eq!(body["max_tokens"], 1024);
+    assert!(body.get("tools").is_none());
+}
+
+#[test]
+fn asks_for_json_in_the_system_prompt() {
+    let body = anthropic().generate_request_body("system", "user", &OutputFormat::Json).unwrap();
+
+    let system = body["system"].as_str().unwrap();
+    assert!(system.starts_with("system\n\n"));
+    assert!(system.contains("JSON"));
+}
+
+#[test]
+fn forces_a_tool_call_for_strict_json() {
+    let format = OutputFormat::StrictJson(INSTRUCTION_RESPONSE_FORMAT.clone());
+
+    let body = anthropic().generate_request_body("system", "user", &format).unwrap();
+
+    assert_eq!(body["tools"][0]["name"], "instruction");
+    assert_eq!(body["tools"][0]["input_schema"], INSTRUCTION_RESPONSE_FORMAT["json_schema"]["schema"]);
+    assert_eq!(body["tool_choice"], serde_json::json!({"type": "tool", "name": "instruction"}));
+    assert_eq!(body["system"], "system");
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::temp_dir;
+use lib::adapters::cache::{CacheMode, CachedLLM};
+use lib::adapters::llm::LLMInterface;
+use lib::adapters::mock::MockProvider;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A cache over a mock that answers with the number of requests it has received so far.
+fn cached(dir: &Path, mode: CacheMode, requests: &Arc<AtomicUsize>) -> CachedLLM<MockProvider> {
+    let requests = requests.clone();
+    let llm = MockProvider::new()
+        .with_handler(move |_| Ok(format!("response {}", requests.fetch_add(1, Ordering::SeqCst) + 1)));
+    CachedLLM::new(llm, dir).with_mode(mode)
+}
+
+async fn send(llm: &CachedLLM<MockProvider>, user_prompt: &str) -> anyhow::Result<String> {
+    llm.send_request("system", user_prompt).await
+}
+
+#[tokio::test]
+async fn read_write_sends_each_request_once() {
+    let dir = temp_dir("cache-read-write");
+    let requests = Arc::new(AtomicUsize::new(0));
+    let llm = cached(&dir, CacheMode::ReadWrite, &requests);
+
+    assert_eq!(send(&llm, "first").await.unwrap(), "response 1");
+    assert_eq!(send(&llm, "first").await.unwrap(), "response 1");
+    assert_eq!(send(&llm, "second").await.unwrap(), "response 2");
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn cache_only_never_sends_requests() {
+    let dir = temp_dir("cache-only");
+    let requests = Arc::new(AtomicUsize::new(0));
+    send(&cached(&dir, CacheMode::ReadWrite, &requests), "cached").await.unwrap();
+    let llm = cached(&dir, CacheMode::CacheOnly, &requests);
+
+    assert_eq!(send(&llm, "cached").await.unwrap(), "response 1");
+    assert!(send(&llm, "missing").await.is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn refresh_sends_every_request_and_overwrites_the_cache() {
+    let dir = temp_dir("cache-refresh");
+    let requests = Arc::new(AtomicUsize::new(0));
+    send(&cached(&dir, CacheMode::ReadWrite, &requests), "prompt").await.unwrap();
+    let llm = cached(&dir, CacheMode::Refresh, &requests);
+
+    assert_eq!(send(&llm, "prompt").await.unwrap(), "response 2");
+    assert_eq!(send(&llm, "prompt").await.unwrap(), "response 3");
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+    assert_eq!(send(&cached(&dir, CacheMode::CacheOnly, &requests), "prompt").await.unwrap(), "response 3");
+}
+
+#[tokio::test]
+async fn bypass_neither_reads_nor_writes_the_cache() {
+    let dir = temp_dir("cache-bypass");
+    let requests = Arc::new(AtomicUsize::new(0));
+    send(&cached(&dir, CacheMode::ReadWrite, &requests), "cached").await.unwrap();
+    let llm = cached(&dir, CacheMode::Bypass, &requests);
+
+    assert_eq!(send(&llm, "cached").await.unwrap(), "response 2");
+    assert_eq!(send(&llm, "uncached").await.unwrap(), "response 3");
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+    assert!(send(&cached(&dir, CacheMode::CacheOnly, &requests), "uncached").await.is_err());
+}
@@ -0,0 +1,44 @@
+use lib::adapters::input::{tokenizer_for_model, ChunkUnit, Document, DocumentChunker, Splitter};
+
+const TEXT: &str = "one two three four five six seven eight nine ten";
+const MODEL: &str = "gpt-4o-mini-2024-07-18";
+
+fn chunk(size: usize, overlap: usize) -> Vec<(usize, usize, String)> {
+    let chunker = DocumentChunker::new(size, ChunkUnit::Tokens, overlap, Splitter::Text, Some(MODEL)).unwrap();
+    chunker.chunk_document(&Document::from(TEXT.to_string()))
+        .into_iter()
+        .map(|chunk| (chunk.start, chunk.end, chunk.text.to_string()))
+        .collect()
+}
+
+#[test]
+fn sizes_chunks_in_tokens() {
+    // Every word is one token of the model's tokenizer
+    assert_eq!(tokenizer_for_model(Some(MODEL)).unwrap().encode_ordinary(TEXT).len(), 10);
+
+    assert_eq!(chunk(5, 0), [
+        (0, 23, "one two three four five".to_string()),
+        (24, 48, "six seven eight nine ten".to_string()),
+    ]);
+}
+
+#[test]
+fn overlaps_consecutive_chunks() {
+    let chunks = chunk(5, 2);
+
+    // Each chunk repeats the last two tokens of the one before it
+    assert_eq!(chunks, [
+        (0, 23, "one two three four five".to_string()),
+        (14, 39, "four five six seven eight".to_string()),
+        (28, 48, "seven eight nine ten".to_string()),
+    ]);
+    assert!(chunks.iter().all(|(start, end, text)| &TEXT[*start..*end] == text));
+}
+
+#[test]
+fn rejects_overlap_not_smaller_than_size() {
+    for overlap in [5, 6] {
+        assert!(DocumentChunker::new(5, ChunkUnit::Tokens, overlap, Splitter::Text, Some(MODEL)).is_err());
+        assert!(DocumentChunker::new(5, ChunkUnit::Chars, overlap, Splitter::Text, None).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "docx")]
+
+mod common;
+
+use common::temp_dir;
+use lib::adapters::readers::docx::DocxReader;
+use lib::adapters::readers::DocumentReader;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const NAMESPACES: &str = r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:mc="http://schemas.openxmlformats.org/markup-compatibility/2006""#;
+
+fn write_docx(path: &Path, body: &str, styles: Option<&str>) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    zip.start_file("word/document.xml", SimpleFileOptions::default()).unwrap();
+    write!(zip, r#"<w:document {}><w:body>{}</w:body></w:document>"#, NAMESPACES, body).unwrap();
+    if let Some(styles) = styles {
+        zip.start_file("word/styles.xml", SimpleFileOptions::default()).unwrap();
+        write!(zip, r#"<w:styles {}>{}</w:styles>"#, NAMESPACES, styles).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+fn paragraph(style: Option<&str>, text: &str) -> String {
+    let properties = style.map(|style| format!(r#"<w:pPr><w:pStyle w:val="{}"/></w:pPr>"#, style)).unwrap_or_default();
+    format!("<w:p>{}<w:r><w:t>{}</w:t></w:r></w:p>", properties, text)
+}
+
+#[test]
+fn splits_sections_at_english_headings_without_styles() {
+    let dir = temp_dir("docx-english");
+    let path = dir.join("report.docx");
+    let body = [
+        paragraph(Some("Title"), "Report"),
+        paragraph(None, "Summary."),
+        paragraph(Some("Heading1"), "Findings"),
+        paragraph(None, "Details."),
+        paragraph(Some("Heading3"), "Minor point"),
+        paragraph(None, "More details."),
+    ].concat();
+    write_docx(&path, &body, None);
+
+    let sections = DocxReader.read(&path).unwrap();
+
+    let titles: Vec<Option<&str>> = sections.iter().map(|section| section.title.as_deref()).collect();
+    assert_eq!(titles, [Some("Report"), Some("Findings")]);
+    assert_eq!(sections[1].text, "Findings\nDetails.\nMinor point\nMore details.");
+}
+
+#[test]
+fn splits_sections_at_localized_heading_styles() {
+    let dir = temp_dir("docx-localized");
+    let path = dir.join("bericht.docx");
+    let styles = r#"<w:style w:type="paragraph" w:styleId="Titel"><w:name w:val="Title"/></w:style>
+        <w:style w:type="paragraph" w:styleId="berschrift1"><w:name w:val="heading 1"/></w:style>
+        <w:style w:type="paragraph" w:styleId="Kapitel"><w:name w:val="Kapitel"/><w:pPr><w:outlineLvl w:val="1"/></w:pPr></w:style>"#;
+    let body = [
+        paragraph(Some("Titel"), "Bericht"),
+        paragraph(None, "Zusammenfassung."),
+        paragraph(Some("berschrift1"), "Ergebnisse"),
+        paragraph(None, "Einzelheiten."),
+        paragraph(Some("Kapitel"), "Anhang"),
+        paragraph(None, "Tabellen."),
+    ].concat();
+    write_docx(&path, &body, Some(styles));
+
+    let sections = DocxReader.read(&path).unwrap();
+
+    let titles: Vec<Option<&str>> = sections.iter().map(|section| section.title.as_deref()).collect();
+    assert_eq!(titles, [Some("Bericht"), Some("Ergebnisse"), Some("Anhang")]);
+}
+
+#[test]
+fn reads_text_box_paragraphs_once() {
+    let dir = temp_dir("docx-text-box");
+    let path = dir.join("flyer.docx");
+    let text_box = r#"<w:p><w:r><w:t>Before the box.</w:t></w:r><w:r><mc:AlternateContent>
+        <mc:Choice Requires="wps"><w:drawing><w:txbxContent><w:p><w:r><w:t>Inside the box.</w:t></w:r></w:p></w:txbxContent></w:drawing></mc:Choice>
+        <mc:Fallback><w:pict><w:txbxContent><w:p><w:r><w:t>Inside the box.</w:t></w:r></w:p></w:txbxContent></w:pict></mc:Fallback>
+        </mc:AlternateContent></w:r></w:p>"#;
+    write_docx(&path, &[text_box, &paragraph(None, "After the box.")].concat(), None);
+
+    let sections = DocxReader.read(&path).unwrap();
+
+    assert_eq!(sections.len(), 1);
+    assert_eq!(sections[0].text, "Before the box.\nInside the box.\nAfter the box.");
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "epub")]
+
+mod common;
+
+use common::temp_dir;
+use lib::adapters::readers::epub::EpubReader;
+use lib::adapters::readers::DocumentReader;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+fn write_epub(path: &Path, entries: &[(&str, &str)]) {
+    let mut zip = ZipWriter::new(File::create(path).unwrap());
+    for (name, content) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container xmlns="urn:oasis:names:tc:opendocument:xmlns:container" version="1.0">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#;
+
+const PACKAGE: &str = r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <manifest>
+    <item id="one" href="text/chapter%201%20%E2%80%93%20d%C3%A9but.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="text/chapter%232.xhtml#start" media-type="application/xhtml+xml"/>
+    <item id="notes" href="text/notes.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine><itemref idref="one"/><itemref idref="two"/><itemref idref="notes" linear="no"/></spine>
+</package>"#;
+
+fn chapter(title: &str, body: &str) -> String {
+    format!("<html><body><h1>{}</h1><p>{}</p></body></html>", title, body)
+}
+
+#[test]
+fn reads_chapters_with_escaped_hrefs_in_spine_order() {
+    let dir = temp_dir("epub-hrefs");
+    let path = dir.join("book.epub");
+    write_epub(&path, &[
+        ("META-INF/container.xml", CONTAINER),
+        ("OEBPS/content.opf", PACKAGE),
+        ("OEBPS/text/chapter 1 – début.xhtml", &chapter("Beginning", "It starts.")),
+        ("OEBPS/text/chapter#2.xhtml", &chapter("Middle", "It goes on.")),
+        ("OEBPS/text/notes.xhtml", &chapter("Notes", "Not part of the reading order.")),
+    ]);
+
+    let sections = EpubReader.read(&path).unwrap();
+
+    let titles: Vec<Option<&str>> = sections.iter().map(|section| section.title.as_deref()).collect();
+    assert_eq!(titles, [Some("Beginning"), Some("Middle")]);
+    assert_eq!(sections[1].text, "# Middle\n\nIt goes on.");
+}
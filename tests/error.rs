@@ -0,0 +1,77 @@
+use lib::adapters::error::{api_key_from_env, find_llm_error, retry_after_from_headers, LLMError};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use tokio::time::Duration;
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+fn classify(status: u16, body: &str) -> LLMError {
+    LLMError::from_response(StatusCode::from_u16(status).unwrap(), &HeaderMap::new(), body.to_string())
+}
+
+#[test]
+fn reads_retry_after_headers() {
+    assert_eq!(retry_after_from_headers(&headers(&[("retry-after", "2")])), Some(Duration::from_secs(2)));
+    assert_eq!(retry_after_from_headers(&headers(&[("retry-after", "1.5")])), Some(Duration::from_millis(1500)));
+    // The millisecond header is more precise and wins
+    assert_eq!(
+        retry_after_from_headers(&headers(&[("retry-after-ms", "250"), ("retry-after", "1")])),
+        Some(Duration::from_millis(250))
+    );
+    // A date already past gives no wait, so the usual backoff applies
+    assert_eq!(retry_after_from_headers(&headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")])), None);
+    assert_eq!(retry_after_from_headers(&headers(&[("retry-after", "soon")])), None);
+    assert_eq!(retry_after_from_headers(&HeaderMap::new()), None);
+}
+
+#[test]
+fn reads_retry_after_http_dates() {
+    let date = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+    let retry_after = retry_after_from_headers(&headers(&[("retry-after", &date)])).unwrap();
+    assert!(retry_after > Duration::from_secs(25) && retry_after <= Duration::from_secs(30));
+}
+
+#[test]
+fn classifies_responses_by_status() {
+    assert_eq!(classify(401, "bad key").kind(), "auth");
+    assert_eq!(classify(403, "forbidden").kind(), "auth");
+    assert_eq!(classify(400, "bad schema").kind(), "invalid_request");
+    assert_eq!(classify(404, "no such model").kind(), "invalid_request");
+    assert_eq!(classify(408, "timeout").kind(), "transient");
+    assert_eq!(classify(409, "conflict").kind(), "transient");
+    assert_eq!(classify(500, "oops").kind(), "transient");
+    assert_eq!(classify(503, "overloaded").kind(), "transient");
+}
+
+#[test]
+fn classifies_responses_by_error_code() {
+    assert_eq!(classify(429, r#"{"error":{"code":"insufficient_quota"}}"#).kind(), "auth");
+    assert_eq!(classify(400, r#"{"error":{"code":"content_filter"}}"#).kind(), "content_filtered");
+    assert_eq!(classify(400, r#"{"error":{"type":"content_policy_violation"}}"#).kind(), "content_filtered");
+}
+
+#[test]
+fn rate_limits_carry_retry_after() {
+    let error = LLMError::from_response(StatusCode::TOO_MANY_REQUESTS, &headers(&[("retry-after", "3")]), "slow down".to_string());
+
+    assert_eq!(error, LLMError::RateLimited { message: "slow down".to_string(), retry_after: Some(Duration::from_secs(3)) });
+    assert!(error.is_retryable());
+}
+
+#[test]
+fn treats_empty_api_keys_as_missing() {
+    std::env::set_var("NEURALASSIMILATOR_TEST_EMPTY_KEY", "  ");
+    std::env::set_var("NEURALASSIMILATOR_TEST_KEY", "sk-test");
+
+    for name in ["NEURALASSIMILATOR_TEST_EMPTY_KEY", "NEURALASSIMILATOR_TEST_UNSET_KEY"] {
+        let error = api_key_from_env(name).unwrap_err();
+        assert!(matches!(find_llm_error(&error), Some(LLMError::Auth(_))));
+    }
+    assert_eq!(api_key_from_env("NEURALASSIMILATOR_TEST_KEY").unwrap(), "sk-test");
+}
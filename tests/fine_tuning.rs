@@ -0,0 +1,128 @@
+mod common;
+
+use clap::Parser;
+use common::temp_dir;
+use lib::adapters::error::LLMError;
+use lib::adapters::llm::LLMInterface;
+use lib::adapters::mock::MockProvider;
+use lib::core::fine_tuning::{split_dataset, wait_for_job, FineTuneConfig};
+use lib::utils::lib::Args;
+use lib::utils::llm::FromLLMResponse;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::time::Duration;
+
+fn dataset(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!(r#"{{"messages":[{{"role":"user","content":"question {}"}}]}}"#, i)).collect()
+}
+
+fn read_lines(path: &std::path::Path) -> Vec<String> {
+    fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn split_is_deterministic_for_a_seed() {
+    let dir = temp_dir("split-deterministic");
+    let path = dir.join("dataset.jsonl");
+    fs::write(&path, dataset(40).join("\n")).unwrap();
+
+    let (training_path, validation_path) = split_dataset(&path, 0.25, 42).unwrap();
+    let first = (fs::read(&training_path).unwrap(), fs::read(&validation_path).unwrap());
+    let (training_path, validation_path) = split_dataset(&path, 0.25, 42).unwrap();
+    let second = (fs::read(&training_path).unwrap(), fs::read(&validation_path).unwrap());
+    assert_eq!(first, second);
+
+    // Reordering the dataset does not move lines between the files
+    let mut reversed = dataset(40);
+    reversed.reverse();
+    fs::write(&path, reversed.join("\n")).unwrap();
+    let (_, validation_path) = split_dataset(&path, 0.25, 42).unwrap();
+    let validation: HashSet<String> = read_lines(&validation_path).into_iter().collect();
+    let first_validation: HashSet<String> = String::from_utf8(first.1).unwrap().lines().map(str::to_string).collect();
+    assert_eq!(validation, first_validation);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn split_holds_out_validation_split_of_the_lines() {
+    let dir = temp_dir("split-counts");
+    let path = dir.join("dataset.jsonl");
+    let lines = dataset(40);
+    fs::write(&path, format!("{}\n", lines.join("\n"))).unwrap();
+
+    let (training_path, validation_path) = split_dataset(&path, 0.25, 7).unwrap();
+    let training = read_lines(&training_path);
+    let validation = read_lines(&validation_path);
+    assert_eq!(training.len(), 30);
+    assert_eq!(validation.len(), 10);
+
+    let mut all: Vec<String> = training.into_iter().chain(validation).collect();
+    all.sort();
+    let mut expected = lines;
+    expected.sort();
+    assert_eq!(all, expected);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn split_needs_two_lines() {
+    let dir = temp_dir("split-too-small");
+    let path = dir.join("dataset.jsonl");
+    fs::write(&path, format!("{}\n", dataset(1)[0])).unwrap();
+    assert!(split_dataset(&path, 0.5, 0).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A `MockProvider` whose job status checks fail with a transient error a set number of times.
+struct FlakyJobs {
+    mock: MockProvider,
+    failures: AtomicUsize,
+}
+
+impl LLMInterface for FlakyJobs {
+    async fn send_request<T: FromLLMResponse + Send + Sync>(&self, sys_prompt: &str, user_prompt: &str) -> anyhow::Result<T> {
+        self.mock.send_request(sys_prompt, user_prompt).await
+    }
+
+    async fn upload_file(&self, file_path: PathBuf) -> anyhow::Result<Value> {
+        self.mock.upload_file(file_path).await
+    }
+
+    async fn create_fine_tuning_job(&self, training_file: &str, validation_file: Option<&str>, config: &FineTuneConfig) -> anyhow::Result<Value> {
+        self.mock.create_fine_tuning_job(training_file, validation_file, config).await
+    }
+
+    async fn train(&self, file_path: PathBuf, validation_file_path: Option<PathBuf>, config: &FineTuneConfig) -> anyhow::Result<Value> {
+        self.mock.train(file_path, validation_file_path, config).await
+    }
+
+    async fn get_fine_tuning_job(&self, job_id: &str) -> anyhow::Result<Value> {
+        if self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| failures.checked_sub(1)).is_ok() {
+            return Err(LLMError::Transient("connection reset".to_string()).into());
+        }
+        self.mock.get_fine_tuning_job(job_id).await
+    }
+
+    async fn list_fine_tuning_events(&self, job_id: &str) -> anyhow::Result<Value> {
+        self.mock.list_fine_tuning_events(job_id).await
+    }
+}
+
+#[tokio::test]
+async fn wait_retries_transient_polling_errors() {
+    let llm = FlakyJobs { mock: MockProvider::new(), failures: AtomicUsize::new(2) };
+
+    let job = wait_for_job(&llm, "ftjob-mock", Duration::from_millis(1)).await.unwrap();
+
+    assert_eq!(job["status"], "succeeded");
+    assert_eq!(llm.failures.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn rejects_zero_poll_interval() {
+    assert!(Args::try_parse_from(["neuralassimilator", "--poll-interval", "0"]).is_err());
+    assert_eq!(Args::try_parse_from(["neuralassimilator", "--poll-interval", "1"]).unwrap().poll_interval, 1);
+}
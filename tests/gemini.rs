@@ -0,0 +1,71 @@
+use lib::adapters::gemini::{response_schema, Gemini};
+use lib::adapters::llm::{LLMProvider, OutputFormat};
+use lib::utils::llm::INSTRUCTION_RESPONSE_FORMAT;
+use serde_json::json;
+
+#[test]
+fn translates_nested_objects_and_arrays() {
+    let schema = json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "title": {"type": "string", "description": "Chapter title"},
+            "sections": {
+                "type": "array",
+                "minItems": 1,
+                "items": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {
+                        "heading": {"type": ["string", "null"]},
+                        "tags": {"type": "array", "items": {"type": "string", "pattern": "^[a-z]+$"}},
+                    },
+                    "required": ["heading", "tags"],
+                },
+            },
+        },
+        "required": ["title", "sections"],
+    });
+
+    assert_eq!(response_schema(&schema), json!({
+        "type": "object",
+        "properties": {
+            "title": {"type": "string", "description": "Chapter title"},
+            "sections": {
+                "type": "array",
+                "minItems": 1,
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "heading": {"type": "string", "nullable": true},
+                        "tags": {"type": "array", "items": {"type": "string"}},
+                    },
+                    "required": ["heading", "tags"],
+                },
+            },
+        },
+        "required": ["title", "sections"],
+    }));
+}
+
+#[test]
+fn drops_unsupported_keywords() {
+    let schema = json!({"$schema": "http://json-schema.org/draft-07/schema#", "type": "string", "minLength": 1, "enum": ["a", "b"]});
+
+    assert_eq!(response_schema(&schema), json!({"type": "string", "enum": ["a", "b"]}));
+}
+
+#[test]
+fn sends_strict_json_as_response_schema() {
+    let gemini = Gemini::new("gemini-2.0-flash".to_string(), 0.5, 1024);
+    let format = OutputFormat::StrictJson(INSTRUCTION_RESPONSE_FORMAT.clone());
+
+    let body = gemini.generate_request_body("system", "user", &format).unwrap();
+
+    assert_eq!(body["systemInstruction"]["parts"][0]["text"], "system");
+    assert_eq!(body["contents"][0]["parts"][0]["text"], "user");
+    assert_eq!(body["generationConfig"]["maxOutputTokens"], 1024);
+    assert_eq!(body["generationConfig"]["responseMimeType"], "application/json");
+    assert_eq!(body["generationConfig"]["responseSchema"], response_schema(&INSTRUCTION_RESPONSE_FORMAT["json_schema"]["schema"]));
+    assert!(body["generationConfig"]["responseSchema"].get("additionalProperties").is_none());
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use common::temp_dir;
+use lib::adapters::cassette::CassetteProvider;
+use lib::adapters::error::LLMError;
+use lib::adapters::input::FileInputAdapter;
+use lib::adapters::llm::{LLMInterface, OutputFormat};
+use lib::adapters::mock::{MockProvider, MockRequest};
+use lib::core::assimilator::{Assimilator, MIN_PAIRS_BEFORE_ABORT};
+use lib::core::report::HarvestReport;
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const FIXTURE_CASSETTE: &str = "tests/harvest.cassette.jsonl";
+const FIXTURE_INPUT: &str = "tests/fixtures/harvest";
+const USE_CASE: &str = "Reading comprehension";
+const PROMPTS: [&str; 2] = [
+    "Ask a question about the passage and answer it.",
+    "Summarise the passage in one sentence.",
+];
+
+/// Answers the prompt tuning request with `PROMPTS` and every other request with an instruction
+/// made of the prompt and the first words of the chunk. Chunks containing "forbidden" are filtered.
+fn respond(request: &MockRequest) -> anyhow::Result<String> {
+    if let OutputFormat::StrictJson(schema) = &request.output_format {
+        if schema["json_schema"]["name"] == "prompts" {
+            return Ok(json!({"prompts": PROMPTS}).to_string());
+        }
+    }
+    let (prompt, chunk) = request.user.split_once("\n\n").expect("User prompt holds prompt and chunk");
+    if chunk.contains("forbidden") {
+        return Err(LLMError::ContentFiltered("Passage is forbidden".to_string()).into());
+    }
+    let opening: Vec<&str> = chunk.split_whitespace().take(4).collect();
+    Ok(json!({"instruction": prompt, "response": opening.join(" ")}).to_string())
+}
+
+fn writer(path: &Path) -> Arc<Mutex<BufWriter<File>>> {
+    Arc::new(Mutex::new(BufWriter::new(File::create(path).unwrap())))
+}
+
+fn read_lines(path: &Path) -> Vec<Value> {
+    fs::read_to_string(path).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+struct Harvest {
+    report: HarvestReport,
+    output: Vec<Value>,
+    metadata: Vec<Value>,
+    rejects: Vec<Value>,
+}
+
+impl Harvest {
+    /// Output lines paired with the metadata line describing them, in an order that does not
+    /// depend on which request finished first.
+    fn sorted_pairs(&self) -> Vec<(Value, Value)> {
+        let mut pairs: Vec<(Value, Value)> = self.output.iter().cloned().zip(self.metadata.iter().cloned()).collect();
+        pairs.sort_by_key(|(output, metadata)| (output.to_string(), metadata.to_string()));
+        pairs
+    }
+}
+
+/// Tunes prompts and harvests every file of `input` into `output_dir`, the way `run` does.
+async fn harvest<T: LLMInterface>(llm: T, input: PathBuf, output_dir: &Path) -> Harvest {
+    let output_path = output_dir.join("output.jsonl");
+    let metadata_path = output_dir.join("output.meta.jsonl");
+    let rejects_path = output_dir.join("output.rejects.jsonl");
+
+    let assimilator = Assimilator::new(llm, writer(&output_path))
+        .with_metadata_writer(writer(&metadata_path))
+        .with_rejects_path(rejects_path.clone())
+        .with_concurrency(2);
+    let prompts = assimilator.tune_prompt(USE_CASE).await.unwrap();
+    let report = assimilator.assimilate(FileInputAdapter::new(input, 1000), &prompts).await.unwrap();
+
+    Harvest {
+        report,
+        output: read_lines(&output_path),
+        metadata: read_lines(&metadata_path),
+        rejects: if rejects_path.exists() { read_lines(&rejects_path) } else { Vec::new() },
+    }
+}
+
+#[tokio::test]
+async fn harvests_input_with_mock_provider() {
+    let dir = temp_dir("harvest-mock");
+    let input = dir.join("input");
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join("lighthouse.txt"), "The keeper climbed the steps of the lighthouse at dusk.").unwrap();
+    fs::write(input.join("vault.txt"), "This passage is forbidden and will be filtered.").unwrap();
+
+    let harvest = harvest(MockProvider::new().with_handler(respond), input, &dir).await;
+
+    assert_eq!(harvest.report.succeeded, 2);
+    assert_eq!(harvest.report.failed, 2);
+    assert_eq!(harvest.report.skipped, 0);
+    assert!(!harvest.report.aborted);
+    assert_eq!(harvest.report.failure_causes.into_iter().collect::<Vec<_>>(), vec![("content_filtered".to_string(), 2)]);
+
+    assert_eq!(harvest.output.len(), 2);
+    assert_eq!(harvest.metadata.len(), 2);
+    for (output, metadata) in harvest.output.iter().zip(&harvest.metadata) {
+        let messages = output["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2]["content"], "The keeper climbed the");
+        // Line N of the sidecar describes line N of the output
+        let prompt_index = metadata["prompt_index"].as_u64().unwrap() as usize;
+        assert_eq!(messages[1]["content"], PROMPTS[prompt_index]);
+        assert!(metadata["source"].as_str().unwrap().ends_with("lighthouse.txt"));
+        assert_eq!(metadata["section"], 0);
+        assert_eq!(metadata["provider"], "mock");
+    }
+
+    assert_eq!(harvest.rejects.len(), 2);
+    for reject in &harvest.rejects {
+        assert!(reject["source"].as_str().unwrap().ends_with("vault.txt"));
+        assert!(reject["error"].as_str().unwrap().contains("Passage is forbidden"));
+    }
+}
+
+/// Harvests `files` one-line files with a provider that fails every instruction request.
+async fn harvest_failing(name: &str, files: usize) -> HarvestReport {
+    let dir = temp_dir(name);
+    let input = dir.join("input");
+    fs::create_dir(&input).unwrap();
+    for i in 0..files {
+        fs::write(input.join(format!("{}.txt", i)), format!("This forbidden passage is number {}.", i)).unwrap();
+    }
+
+    let assimilator = Assimilator::new(MockProvider::new().with_handler(respond), writer(&dir.join("output.jsonl")))
+        .with_concurrency(1)
+        .with_max_failure_rate(0.5);
+    let prompts = assimilator.tune_prompt(USE_CASE).await.unwrap();
+    assimilator.assimilate(FileInputAdapter::new(input, 1000), &prompts).await.unwrap()
+}
+
+#[tokio::test]
+async fn stops_early_above_max_failure_rate() {
+    let report = harvest_failing("harvest-abort", 15).await;
+
+    assert!(report.aborted);
+    assert_eq!(report.failed, MIN_PAIRS_BEFORE_ABORT);
+    assert_eq!(report.completed(), MIN_PAIRS_BEFORE_ABORT);
+}
+
+#[tokio::test]
+async fn completes_small_runs_above_max_failure_rate() {
+    let report = harvest_failing("harvest-small-failing", 2).await;
+
+    assert!(!report.aborted);
+    assert_eq!(report.failed, 4);
+    assert_eq!(report.failure_rate(), 1.0);
+}
+
+#[tokio::test]
+async fn replays_recorded_harvest() {
+    let dir = temp_dir("harvest-cassette");
+    let cassette = dir.join("harvest.cassette.jsonl");
+    let (recording, replay) = (dir.join("recording"), dir.join("replay"));
+    fs::create_dir(&recording).unwrap();
+    fs::create_dir(&replay).unwrap();
+
+    let llm = CassetteProvider::record(MockProvider::new().with_handler(respond), &cassette).unwrap();
+    let recorded = harvest(llm, PathBuf::from(FIXTURE_INPUT), &recording).await;
+    let replayed = harvest(CassetteProvider::replay(&cassette).unwrap(), PathBuf::from(FIXTURE_INPUT), &replay).await;
+
+    assert_eq!(recorded.report.succeeded, 4);
+    assert_eq!(replayed.report.succeeded, 4);
+    assert_eq!(replayed.report.failed, 0);
+    assert_eq!(replayed.sorted_pairs(), recorded.sorted_pairs());
+}
+
+#[tokio::test]
+async fn replay_fails_unrecorded_requests() {
+    let dir = temp_dir("harvest-unrecorded");
+    let input = dir.join("input");
+    fs::create_dir(&input).unwrap();
+    fs::write(input.join("new.txt"), "A passage the cassette has never seen.").unwrap();
+
+    let harvest = harvest(CassetteProvider::replay(FIXTURE_CASSETTE).unwrap(), input, &dir).await;
+
+    assert_eq!(harvest.report.succeeded, 0);
+    assert_eq!(harvest.report.failed, 2);
+    assert!(harvest.output.is_empty());
+    assert!(harvest.rejects.iter().all(|reject| reject["error"].as_str().unwrap().contains("No interaction for request")));
+}
+
+#[tokio::test]
+async fn replays_fixture_cassette() {
+    let dir = temp_dir("harvest-fixture");
+
+    let harvest = harvest(CassetteProvider::replay(FIXTURE_CASSETTE).unwrap(), PathBuf::from(FIXTURE_INPUT), &dir).await;
+
+    assert_eq!(harvest.report.succeeded, 4);
+    assert_eq!(harvest.report.failed, 0);
+    let mut instructions: Vec<&str> = harvest.output.iter()
+        .map(|output| output["messages"][1]["content"].as_str().unwrap())
+        .collect();
+    instructions.sort();
+    assert_eq!(instructions, [PROMPTS[0], PROMPTS[0], PROMPTS[1], PROMPTS[1]]);
+    assert!(harvest.metadata.iter().all(|metadata| metadata["provider"] == "mock"));
+}
+
+/// Re-records `tests/harvest.cassette.jsonl` after the prompts or the fixture input change:
+/// `cargo test --test harvest -- --ignored`.
+#[tokio::test]
+#[ignore]
+async fn record_fixture_cassette() {
+    let dir = temp_dir("harvest-record");
+    let llm = CassetteProvider::record(MockProvider::new().with_handler(respond), FIXTURE_CASSETTE).unwrap();
+    let harvest = harvest(llm, PathBuf::from(FIXTURE_INPUT), &dir).await;
+    assert_eq!(harvest.report.succeeded, 4);
+}
@@ -0,0 +1,18 @@
+#![cfg(feature = "html")]
+
+use lib::adapters::readers::html::html_to_text;
+
+#[test]
+fn renders_every_article() {
+    let html = "<html><body><nav>Menu</nav>\
+        <article><h1>First</h1><p>one</p></article>\
+        <article><h1>Second</h1><p>two</p></article>\
+        </body></html>";
+    assert_eq!(html_to_text(html), "# First\n\none\n\n# Second\n\ntwo");
+}
+
+#[test]
+fn renders_nested_articles_once() {
+    let html = "<html><body><main><h1>Blog</h1><article><p>post</p></article></main></body></html>";
+    assert_eq!(html_to_text(html), "# Blog\n\npost");
+}
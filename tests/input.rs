@@ -0,0 +1,76 @@
+mod common;
+
+use common::temp_dir;
+use lib::adapters::input::{ChunkUnit, Document, DocumentChunker, FileInputAdapter, InputAdapter, Section, Splitter, TextFormat};
+use std::fs;
+
+#[cfg(unix)]
+#[test]
+fn skips_symlink_loops() {
+    let dir = temp_dir("symlink-loop");
+    fs::create_dir(dir.join("nested")).unwrap();
+    fs::write(dir.join("nested/notes.txt"), "Some notes").unwrap();
+    std::os::unix::fs::symlink(&dir, dir.join("nested/loop")).unwrap();
+
+    let adapter = FileInputAdapter::new(dir.clone(), 1000)
+        .with_recursive(true)
+        .with_follow_symlinks(true);
+    let documents = adapter.fetch_documents().unwrap();
+
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].source, Some(dir.join("nested/notes.txt")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fails_on_missing_input_folder() {
+    let adapter = FileInputAdapter::new(temp_dir("missing").join("absent"), 1000);
+    assert!(adapter.fetch_documents().is_err());
+}
+
+#[test]
+fn skips_unreadable_files() {
+    let dir = temp_dir("unreadable-file");
+    fs::write(dir.join("binary.txt"), [0xff, 0xfe, 0x00, 0x80]).unwrap();
+    fs::write(dir.join("notes.txt"), "Some notes").unwrap();
+
+    let documents = FileInputAdapter::new(dir.clone(), 1000).fetch_documents().unwrap();
+
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].source, Some(dir.join("notes.txt")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "pdf")]
+#[test]
+fn skips_malformed_pdfs() {
+    let dir = temp_dir("malformed-pdf");
+    fs::write(dir.join("broken.pdf"), b"%PDF-1.4\n1 0 obj << /Type /Catalog /Pages 2 0 R >>\ntrailer << /Root 1 0 R >>\n%%EOF").unwrap();
+    fs::write(dir.join("notes.txt"), "Some notes").unwrap();
+
+    let documents = FileInputAdapter::new(dir.clone(), 1000).fetch_documents().unwrap();
+
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0].source, Some(dir.join("notes.txt")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn offsets_chunks_within_their_section() {
+    let document = Document {
+        source: None,
+        format: TextFormat::Plain,
+        sections: vec![
+            Section::new("First page.".to_string()).with_page(1),
+            Section::new("Second page.".to_string()).with_page(2),
+        ],
+    };
+    let chunker = DocumentChunker::new(100, ChunkUnit::Chars, 0, Splitter::Auto, None).unwrap();
+
+    let chunks = chunker.chunk_document(&document);
+
+    let located: Vec<(usize, Option<u32>, usize, usize)> = chunks.iter()
+        .map(|chunk| (chunk.section, chunk.page, chunk.start, chunk.end))
+        .collect();
+    assert_eq!(located, [(0, Some(1), 0, 11), (1, Some(2), 0, 12)]);
+}
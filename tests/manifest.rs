@@ -0,0 +1,102 @@
+mod common;
+
+use common::temp_dir;
+use lib::adapters::input::{FileInputAdapter, InputAdapter};
+use lib::adapters::mock::MockProvider;
+use lib::core::assimilator::Assimilator;
+use lib::core::manifest::{count_lines, truncate_partial_line, RunManifest};
+use lib::core::report::HarvestReport;
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const PROMPTS: [&str; 2] = ["Ask a question about the passage.", "Summarise the passage."];
+
+#[test]
+fn truncates_a_partial_last_line() {
+    let dir = temp_dir("manifest-truncate");
+    let path = dir.join("output.jsonl");
+    fs::write(&path, "{\"a\":1}\n{\"b\":2}\n{\"c\":").unwrap();
+
+    assert_eq!(count_lines(&path).unwrap(), 2);
+    truncate_partial_line(&path).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}\n{\"b\":2}\n");
+    assert_eq!(count_lines(&path).unwrap(), 2);
+}
+
+#[test]
+fn keeps_complete_files() {
+    let dir = temp_dir("manifest-complete");
+    let path = dir.join("output.jsonl");
+    fs::write(&path, "{\"a\":1}\n").unwrap();
+
+    truncate_partial_line(&path).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":1}\n");
+}
+
+#[test]
+fn keys_identical_chunks_of_different_files_apart() {
+    let dir = temp_dir("manifest-key");
+    fs::write(dir.join("a.txt"), "The same passage.").unwrap();
+    fs::write(dir.join("b.txt"), "The same passage.").unwrap();
+
+    let chunks = FileInputAdapter::new(dir, 1000).fetch_chunks().unwrap();
+
+    assert_eq!(chunks[0].hash, chunks[1].hash);
+    assert_ne!(RunManifest::pair_key(&chunks[0], PROMPTS[0]), RunManifest::pair_key(&chunks[1], PROMPTS[0]));
+}
+
+/// Harvests `input` into `output_path`, appending when resuming, with a provider that answers the
+/// first `succeed` instruction requests and fails the rest. Returns the report and requests made.
+async fn harvest(input: &Path, output_path: &Path, manifest: RunManifest, succeed: usize) -> (HarvestReport, usize) {
+    let file = OpenOptions::new().create(true).append(true).open(output_path).unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let llm = MockProvider::new().with_handler(move |_| {
+        if counter.fetch_add(1, Ordering::SeqCst) < succeed {
+            Ok(json!({"instruction": "instruction", "response": "response"}).to_string())
+        } else {
+            Err(anyhow::anyhow!("Interrupted"))
+        }
+    });
+    let assimilator = Assimilator::new(llm, Arc::new(Mutex::new(BufWriter::new(file))))
+        .with_manifest(manifest)
+        .with_concurrency(1);
+    let prompts: Vec<String> = PROMPTS.iter().map(|prompt| prompt.to_string()).collect();
+    let report = assimilator.assimilate(FileInputAdapter::new(input.to_path_buf(), 1000), &prompts).await.unwrap();
+    (report, requests.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn resumes_an_interrupted_harvest() {
+    let dir = temp_dir("manifest-resume");
+    let input = dir.join("input");
+    fs::create_dir(&input).unwrap();
+    // Identical files, so a key without the source would mark the second one as done
+    fs::write(input.join("a.txt"), "The same passage.").unwrap();
+    fs::write(input.join("b.txt"), "The same passage.").unwrap();
+    let output_path = dir.join("output.jsonl");
+    let manifest_path = dir.join("output.manifest.jsonl");
+
+    // The first run completes the pairs of a.txt, then is interrupted mid-write
+    let (report, _) = harvest(&input, &output_path, RunManifest::create(manifest_path.clone()).unwrap(), 2).await;
+    assert_eq!((report.succeeded, report.failed), (2, 2));
+    for path in [&output_path, &manifest_path] {
+        write!(OpenOptions::new().append(true).open(path).unwrap(), "{{\"partial").unwrap();
+    }
+
+    truncate_partial_line(&output_path).unwrap();
+    let (report, requests) = harvest(&input, &output_path, RunManifest::open(manifest_path.clone()).unwrap(), usize::MAX).await;
+
+    assert_eq!(report.skipped, 2);
+    assert_eq!(report.succeeded, 2);
+    assert_eq!(requests, 2);
+    assert_eq!(count_lines(&output_path).unwrap(), 4);
+    assert_eq!(count_lines(&manifest_path).unwrap(), 4);
+}
@@ -0,0 +1,58 @@
+use lib::utils::rate_limit::{parse_reset_duration, RateLimiter};
+use std::time::Instant;
+use tokio::time::Duration;
+
+#[test]
+fn parses_reset_durations() {
+    assert_eq!(parse_reset_duration("6m0s"), Some(Duration::from_secs(360)));
+    assert_eq!(parse_reset_duration("250ms"), Some(Duration::from_millis(250)));
+    assert_eq!(parse_reset_duration("1h2m3.5s"), Some(Duration::from_secs_f64(3723.5)));
+    assert_eq!(parse_reset_duration("2"), Some(Duration::from_secs(2)));
+}
+
+#[test]
+fn rejects_malformed_reset_durations() {
+    assert_eq!(parse_reset_duration(""), None);
+    assert_eq!(parse_reset_duration("soon"), None);
+    assert_eq!(parse_reset_duration("5x"), None);
+    assert_eq!(parse_reset_duration("1.2.3s"), None);
+}
+
+/// 600,000 tokens per minute refill 10 tokens per millisecond.
+const TOKENS_PER_MINUTE: u32 = 600_000;
+
+async fn elapsed(future: impl std::future::Future<Output = ()>) -> Duration {
+    let start = Instant::now();
+    future.await;
+    start.elapsed()
+}
+
+#[tokio::test]
+async fn reservations_wait_for_the_budget() {
+    let limiter = RateLimiter::new(None, Some(TOKENS_PER_MINUTE));
+
+    assert!(elapsed(limiter.acquire(TOKENS_PER_MINUTE)).await < Duration::from_millis(100));
+    // The budget is spent, so 2,000 more tokens take about 200ms to refill
+    assert!(elapsed(limiter.acquire(2_000)).await >= Duration::from_millis(150));
+}
+
+#[tokio::test]
+async fn settle_returns_unused_tokens() {
+    let limiter = RateLimiter::new(None, Some(TOKENS_PER_MINUTE));
+
+    limiter.acquire(TOKENS_PER_MINUTE).await;
+    limiter.settle(TOKENS_PER_MINUTE, 0).await;
+
+    assert!(elapsed(limiter.acquire(TOKENS_PER_MINUTE)).await < Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn settle_credits_at_most_the_capped_reservation() {
+    let limiter = RateLimiter::new(None, Some(TOKENS_PER_MINUTE));
+
+    // Only the whole budget is reserved, so using 2,000 tokens more than that leaves a debt
+    limiter.acquire(10 * TOKENS_PER_MINUTE).await;
+    limiter.settle(10 * TOKENS_PER_MINUTE, TOKENS_PER_MINUTE + 2_000).await;
+
+    assert!(elapsed(limiter.acquire(1_000)).await >= Duration::from_millis(250));
+}
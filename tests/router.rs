@@ -0,0 +1,76 @@
+use lib::adapters::error::LLMError;
+use lib::adapters::llm::LLMInterface;
+use lib::adapters::mock::MockProvider;
+use lib::adapters::router::Router;
+use std::time::Instant;
+use tokio::time::Duration;
+
+async fn send(router: &Router<MockProvider>) -> String {
+    let (_, provider): (String, String) = router.send_request_attributed("system", "user").await.unwrap();
+    provider
+}
+
+#[tokio::test]
+async fn splits_requests_by_weight() {
+    let router = Router::new()
+        .with_provider(MockProvider::new().with_name("heavy"), 3)
+        .with_provider(MockProvider::new().with_name("light"), 1);
+
+    let mut providers = Vec::new();
+    for _ in 0..8 {
+        providers.push(send(&router).await);
+    }
+
+    // Smooth weighted round-robin interleaves the light provider instead of bunching it up
+    assert_eq!(providers, ["heavy", "heavy", "light", "heavy", "heavy", "heavy", "light", "heavy"]);
+    assert_eq!(providers.iter().filter(|provider| *provider == "heavy").count(), 6);
+}
+
+#[tokio::test]
+async fn fails_over_to_the_next_provider() {
+    let limited = MockProvider::new()
+        .with_name("limited")
+        .with_error(LLMError::RateLimited { message: "slow down".to_string(), retry_after: Some(Duration::from_secs(60)) });
+    let router = Router::new()
+        .with_provider(limited, 1)
+        .with_provider(MockProvider::new().with_name("spare"), 1);
+
+    assert_eq!(send(&router).await, "spare");
+    // The rate-limited provider sits out its Retry-After
+    assert_eq!(send(&router).await, "spare");
+    assert_eq!(send(&router).await, "spare");
+}
+
+#[tokio::test]
+async fn retries_a_transient_error_without_cooling_down() {
+    let flaky = MockProvider::new()
+        .with_name("flaky")
+        .with_error(LLMError::Transient("connection reset".to_string()));
+    let router = Router::new()
+        .with_provider(flaky, 1)
+        .with_delay(Duration::from_millis(1));
+
+    let start = Instant::now();
+    assert_eq!(send(&router).await, "flaky");
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn cools_down_after_repeated_transient_errors() {
+    let mut down = MockProvider::new().with_name("down");
+    for _ in 0..3 {
+        down = down.with_error(LLMError::Transient("connection reset".to_string()));
+    }
+    let router = Router::new()
+        .with_provider(down, 1)
+        .with_provider(MockProvider::new().with_name("spare"), 1);
+
+    let mut providers = Vec::new();
+    for _ in 0..8 {
+        providers.push(send(&router).await);
+    }
+
+    // Every other request picks the failing provider and fails over; after its third failure it
+    // sits out, although it has no errors left to return
+    assert_eq!(providers, ["spare"; 8]);
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "html")]
+
+mod common;
+
+use common::temp_dir;
+use lib::adapters::readers::warc::WarcReader;
+use lib::adapters::readers::DocumentReader;
+use std::fs;
+
+fn record(uri: &str, content_length: &str, block: &str) -> String {
+    format!(
+        "WARC/1.0\r\nWARC-Type: resource\r\nWARC-Target-URI: {}\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{}\r\n\r\n",
+        uri, content_length, block
+    )
+}
+
+#[test]
+fn titles_sections_with_target_uri() {
+    let dir = temp_dir("warc-titles");
+    let first = "<html><body><p>first page</p></body></html>";
+    let second = "<html><body><p>second page</p></body></html>";
+    let path = dir.join("crawl.warc");
+    fs::write(&path, record("https://example.com/a", &first.len().to_string(), first)
+        + &record("https://example.com/b", &second.len().to_string(), second)).unwrap();
+
+    let sections = WarcReader.read(&path).unwrap();
+
+    let titles: Vec<Option<&str>> = sections.iter().map(|section| section.title.as_deref()).collect();
+    assert_eq!(titles, [Some("https://example.com/a"), Some("https://example.com/b")]);
+    assert_eq!(sections[0].text, "first page");
+}
+
+#[test]
+fn rejects_content_length_past_end_of_archive() {
+    let dir = temp_dir("warc-overflow");
+    for content_length in [u64::MAX.to_string(), "4096".to_string()] {
+        let path = dir.join("bogus.warc");
+        fs::write(&path, record("https://example.com/", &content_length, "<p>short</p>")).unwrap();
+
+        let error = WarcReader.read(&path).unwrap_err();
+
+        assert!(format!("{:#}", error).contains("Truncated WARC record"), "{:#}", error);
+    }
+}